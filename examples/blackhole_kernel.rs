@@ -1,7 +1,7 @@
-use hsa::KernelDispatch;
 use hsa::error::{log_debug, log_error, log_info};
+use hsa::{Dim3, KernelDispatch};
 use hsa::{Executable, HsaError};
-use hsa::{HsaContext, Result, Signal};
+use hsa::{HsaContext, Result, Signal, SignalCondition};
 use std::env;
 use std::fs;
 use std::path::Path;
@@ -58,7 +58,10 @@ fn main() -> Result<()> {
             ctx
         }
         Err(e) => {
-            log_error(&format!("Failed to initialize HSA context: {}", e));
+            log_error(&format!(
+                "Failed to initialize HSA context: {}",
+                e.full_message()
+            ));
             return Err(e);
         }
     };
@@ -128,7 +131,7 @@ fn main() -> Result<()> {
 
     // List all available symbols for debugging
     log_info("Discovering available kernel symbols...");
-    match executable.list_symbols(&ctx.agent) {
+    let symbols = match executable.list_symbols(&ctx.agent) {
         Ok(symbols) => {
             if symbols.is_empty() {
                 log_error("No symbols found in executable!");
@@ -141,42 +144,26 @@ fn main() -> Result<()> {
                     log_info(&format!("  {}: {}", i, symbol));
                 }
             }
+            symbols
         }
         Err(e) => {
-            log_error(&format!("Failed to list symbols: {}", e));
+            log_error(&format!("Failed to list symbols: {}", e.full_message()));
+            Vec::new()
         }
-    }
+    };
 
-    // Try to find kernel symbol with various naming patterns
-    let kernel_names = [
-        "trace_geodesics.kd",
-        "trace_geodesics",
-        "__device_kernel__trace_geodesics",
-        "_Z16trace_geodesicsPvS_S_jjj", // Mangled name pattern
-        "blackhole_kernel",
-    ];
-
-    let mut kernel_symbol = None;
-    for name in &kernel_names {
-        log_debug(&format!("Trying kernel name: '{}'", name));
-        match executable.get_kernel_symbol(name, &ctx.agent) {
-            Ok(symbol) => {
-                log_info(&format!("Found kernel symbol: '{}'", name));
-                kernel_symbol = Some(symbol);
-                break;
-            }
-            Err(e) => {
-                log_debug(&format!("Kernel name '{}' not found: {}", name, e));
-            }
-        }
-    }
+    // Older builds of the kernel used a different name; only fall back to it
+    // when the symbols above show no trace_geodesics
+    let has_symbol = |name: &str| symbols.iter().any(|symbol| symbol.contains(name));
+    let kernel_name = if !has_symbol("trace_geodesics") && has_symbol("blackhole_kernel") {
+        "blackhole_kernel"
+    } else {
+        "trace_geodesics"
+    };
+    log_debug(&format!("Looking up kernel '{}'", kernel_name));
 
-    let kernel_symbol = kernel_symbol.ok_or_else(|| {
-        HsaError::KernelNotFound(format!(
-            "Could not find kernel with any of these names: {:?}\nCheck the symbols listed above.",
-            kernel_names
-        ))
-    })?;
+    // Find the kernel symbol, trying the common naming variants
+    let kernel_symbol = executable.find_kernel(kernel_name, &ctx.agent)?;
 
     let kernel_object = kernel_symbol.kernel_object()?;
     log_info(&format!("Kernel object handle: 0x{:x}", kernel_object));
@@ -201,9 +188,9 @@ fn main() -> Result<()> {
     log_info(&format!("Output buffer size: {} bytes", output_size));
 
     // Allocate memory buffers
-    let coarse_region = ctx
-        .coarse_grained_region
-        .ok_or(HsaError::MemoryRegionNotFound)?;
+    let coarse_region = ctx.coarse_grained_region.ok_or_else(|| {
+        HsaError::MemoryRegionNotFound("coarse-grained global region".to_string())
+    })?;
 
     log_debug("Allocating output buffer...");
     let output_buffer = coarse_region.allocate(output_size)?;
@@ -258,7 +245,7 @@ fn main() -> Result<()> {
     let kernarg_region = ctx
         .kernarg_region
         .or(ctx.fine_grained_region)
-        .ok_or(HsaError::MemoryRegionNotFound)?;
+        .ok_or_else(|| HsaError::MemoryRegionNotFound("kernarg region".to_string()))?;
 
     let expected_kernarg_size = std::mem::size_of::<KernelArgs>();
     log_debug(&format!(
@@ -300,26 +287,24 @@ fn main() -> Result<()> {
     log_debug("Completion signal created");
 
     // Set up dispatch parameters
-    let workgroup_size = 16u32;
-    let grid_x = ((width + workgroup_size - 1) / workgroup_size) * workgroup_size;
-    let grid_y = ((height + workgroup_size - 1) / workgroup_size) * workgroup_size;
-
-    log_info(&format!("Dispatch configuration:"));
-    log_info(&format!("  Grid size: {}x{}", grid_x, grid_y));
-    log_info(&format!(
-        "  Workgroup size: {}x{}",
-        workgroup_size, workgroup_size
-    ));
-    log_info(&format!("  Total work items: {}", grid_x * grid_y));
+    let workgroup = ctx.agent.recommended_workgroup_size(2)?;
 
     let dispatch = KernelDispatch {
         kernel_object,
         kernarg_address: kernargs_buffer.as_ptr(),
-        workgroup_size: (workgroup_size as u16, workgroup_size as u16, 1),
-        grid_size: (grid_x, grid_y, 1),
         private_segment_size: private_size,
         group_segment_size: group_size.max(2048), // Use kernel requirement or minimum
+        ..KernelDispatch::for_problem_size(Dim3::new(width, height, 1), workgroup)?
     };
+    let (grid_x, grid_y, _) = dispatch.grid_size;
+
+    log_info(&format!("Dispatch configuration:"));
+    log_info(&format!("  Grid size: {}x{}", grid_x, grid_y));
+    log_info(&format!(
+        "  Workgroup size: {}x{}",
+        workgroup.x, workgroup.y
+    ));
+    log_info(&format!("  Total work items: {}", grid_x * grid_y));
 
     // Get queue
     let queue = ctx.queue.as_ref().ok_or(HsaError::QueueCreationFailed(
@@ -334,9 +319,7 @@ fn main() -> Result<()> {
     log_info("Waiting for kernel completion...");
 
     // Wait for completion with timeout
-    let start_time = std::time::Instant::now();
-    let wait_result = completion_signal.wait_eq(0, u64::MAX);
-    let elapsed = start_time.elapsed();
+    let (wait_result, elapsed) = completion_signal.timed_wait(SignalCondition::Eq, 0, u64::MAX);
 
     if wait_result != 0 {
         log_error(&format!(
@@ -1,13 +1,104 @@
 use crate::bindings;
 use crate::error::{log_debug, log_error, log_info};
-use crate::{HsaError, MemoryRegion, Result};
+use crate::executable::{AUTO_WORKGROUP_BUDGET, auto_workgroup_size};
+use crate::{Dim3, HsaError, MemoryPool, MemoryRegion, PoolAccess, Profile, Result};
+use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::os::raw::c_void;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::str::FromStr;
 
 #[derive(Debug, Clone, Copy)]
 pub struct Agent {
     pub(crate) handle: bindings::hsa_agent_t,
 }
 
+impl PartialEq for Agent {
+    fn eq(&self, other: &Self) -> bool {
+        self.handle.handle == other.handle.handle
+    }
+}
+
+impl Eq for Agent {}
+
+impl Hash for Agent {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.handle.handle.hash(state);
+    }
+}
+
+/// Agent properties checked on every dispatch, cached per queue by
+/// [`Agent::launch_limits`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct LaunchLimits {
+    pub agent: Agent,
+    pub device_type: DeviceType,
+    pub workgroup_max_dim: Dim3,
+    pub workgroup_max_size: u32,
+    pub grid_max_dim: Dim3,
+    pub grid_max_size: u32,
+    /// Scratch bytes a single wavefront can get per work-item, or `None` if
+    /// the runtime does not report the scratch limit.
+    pub scratch_per_work_item: Option<u64>,
+}
+
+impl LaunchLimits {
+    /// See [`Agent::validate_launch`].
+    pub fn check(&self, grid: Dim3, workgroup: Dim3) -> Result<()> {
+        let handle = self.agent.handle.handle;
+
+        if workgroup.volume() == 0 {
+            return Err(HsaError::InvalidArgument(format!(
+                "Workgroup size {:?} has a zero dimension",
+                workgroup
+            )));
+        }
+        if grid.volume() == 0 {
+            return Err(HsaError::InvalidArgument(format!(
+                "Grid size {:?} has a zero dimension",
+                grid
+            )));
+        }
+
+        let max_dim = self.workgroup_max_dim;
+        if workgroup.x > max_dim.x || workgroup.y > max_dim.y || workgroup.z > max_dim.z {
+            return Err(HsaError::InvalidArgument(format!(
+                "Workgroup size {:?} exceeds workgroup_max_dim {:?} of agent 0x{:x}",
+                workgroup, max_dim, handle
+            )));
+        }
+
+        if workgroup.volume() > self.workgroup_max_size as u64 {
+            return Err(HsaError::InvalidArgument(format!(
+                "Workgroup of {} work-items exceeds workgroup_max_size {} of agent 0x{:x}",
+                workgroup.volume(),
+                self.workgroup_max_size,
+                handle
+            )));
+        }
+
+        let max_dim = self.grid_max_dim;
+        if grid.x > max_dim.x || grid.y > max_dim.y || grid.z > max_dim.z {
+            return Err(HsaError::InvalidArgument(format!(
+                "Grid size {:?} exceeds grid_max_dim {:?} of agent 0x{:x}",
+                grid, max_dim, handle
+            )));
+        }
+
+        if grid.volume() > self.grid_max_size as u64 {
+            return Err(HsaError::InvalidArgument(format!(
+                "Grid of {} work-items exceeds grid_max_size {} of agent 0x{:x}",
+                grid.volume(),
+                self.grid_max_size,
+                handle
+            )));
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DeviceType {
     Cpu,
@@ -16,38 +107,153 @@ pub enum DeviceType {
     Aie,
 }
 
+impl From<DeviceType> for bindings::hsa_device_type_t {
+    fn from(device_type: DeviceType) -> Self {
+        match device_type {
+            DeviceType::Cpu => bindings::hsa_device_type_t_HSA_DEVICE_TYPE_CPU,
+            DeviceType::Gpu => bindings::hsa_device_type_t_HSA_DEVICE_TYPE_GPU,
+            DeviceType::Dsp => bindings::hsa_device_type_t_HSA_DEVICE_TYPE_DSP,
+            DeviceType::Aie => bindings::hsa_device_type_t_HSA_DEVICE_TYPE_AIE,
+        }
+    }
+}
+
+impl TryFrom<bindings::hsa_device_type_t> for DeviceType {
+    type Error = HsaError;
+
+    fn try_from(device_type: bindings::hsa_device_type_t) -> Result<Self> {
+        match device_type {
+            bindings::hsa_device_type_t_HSA_DEVICE_TYPE_CPU => Ok(DeviceType::Cpu),
+            bindings::hsa_device_type_t_HSA_DEVICE_TYPE_GPU => Ok(DeviceType::Gpu),
+            bindings::hsa_device_type_t_HSA_DEVICE_TYPE_DSP => Ok(DeviceType::Dsp),
+            bindings::hsa_device_type_t_HSA_DEVICE_TYPE_AIE => Ok(DeviceType::Aie),
+            other => Err(HsaError::InvalidArgument(format!(
+                "Unknown device type: {}",
+                other
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for DeviceType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DeviceType::Cpu => "cpu",
+            DeviceType::Gpu => "gpu",
+            DeviceType::Dsp => "dsp",
+            DeviceType::Aie => "aie",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Parses the names produced by `Display` (`"cpu"`, `"gpu"`, `"dsp"`,
+/// `"aie"`), ignoring ASCII case.
+impl FromStr for DeviceType {
+    type Err = HsaError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "cpu" => Ok(DeviceType::Cpu),
+            "gpu" => Ok(DeviceType::Gpu),
+            "dsp" => Ok(DeviceType::Dsp),
+            "aie" => Ok(DeviceType::Aie),
+            _ => Err(HsaError::InvalidArgument(format!(
+                "Unknown device type '{}', expected one of: cpu, gpu, dsp, aie",
+                s
+            ))),
+        }
+    }
+}
+
+/// Address and signal-value width an agent operates with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MachineModel {
+    /// 32-bit addresses and signal values.
+    Small,
+    /// 64-bit addresses and signal values.
+    Large,
+}
+
+/// Image capabilities of an agent, from [`Agent::image_support`]. The
+/// dimensions are in elements and are all 0 when images are unsupported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ImageSupport {
+    pub supported: bool,
+    pub max_1d: usize,
+    pub max_2d: (usize, usize),
+    pub max_3d: (usize, usize, usize),
+}
+
+/// One level of an agent's cache hierarchy, from [`Agent::iterate_caches`].
+///
+/// HSA reports no per-cache line size or associativity; the agent-wide line
+/// size is [`AgentInfo::cacheline_size`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheInfo {
+    pub name: String,
+    /// 1 for the cache closest to the compute units.
+    pub level: u8,
+    /// Size in bytes.
+    pub size: u32,
+}
+
+/// Snapshot of an agent's properties, from [`Agent::info`].
+#[derive(Debug, Clone)]
+pub struct AgentInfo {
+    pub name: String,
+    pub vendor_name: String,
+    pub device_type: DeviceType,
+    pub profile: Profile,
+    pub machine_model: MachineModel,
+    pub wavefront_size: u32,
+    pub workgroup_max_size: u32,
+    pub queue_max_size: u32,
+    /// Cache line size in bytes, if the runtime reports it.
+    pub cacheline_size: Option<u32>,
+    /// Cache hierarchy, sorted by ascending level.
+    pub caches: Vec<CacheInfo>,
+}
+
 impl Agent {
     pub fn find_gpu() -> Result<Self> {
-        log_debug("Searching for GPU agent...");
+        Self::find(DeviceType::Gpu)
+    }
+
+    pub fn find(device_type: DeviceType) -> Result<Self> {
+        log_debug(&format!("Searching for {:?} agent...", device_type));
 
-        let mut agent_handle = bindings::hsa_agent_t { handle: 0 };
+        let mut search = AgentSearch {
+            device_type: device_type.into(),
+            agent: bindings::hsa_agent_t { handle: 0 },
+        };
 
         unsafe {
             let status = bindings::hsa_iterate_agents(
-                Some(find_gpu_callback),
-                &mut agent_handle as *mut _ as *mut c_void,
+                Some(find_agent_callback),
+                &mut search as *mut _ as *mut c_void,
             );
 
             if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS
                 && status != bindings::hsa_status_t_HSA_STATUS_INFO_BREAK
             {
                 let error = HsaError::from_status_with_context(status, "Failed to iterate agents");
-                log_error(&format!("Agent iteration failed: {}", error));
+                log_error(&format!("Agent iteration failed: {}", error.full_message()));
                 return Err(error);
             }
         }
 
-        if agent_handle.handle == 0 {
-            log_error("No GPU agent found in the system");
+        if search.agent.handle == 0 {
+            log_error(&format!("No {:?} agent found in the system", device_type));
             return Err(HsaError::AgentNotFound);
         }
 
         log_info(&format!(
-            "Found GPU agent with handle: 0x{:x}",
-            agent_handle.handle
+            "Found {:?} agent with handle: 0x{:x}",
+            device_type, search.agent.handle
         ));
         Ok(Agent {
-            handle: agent_handle,
+            handle: search.agent,
         })
     }
 
@@ -65,7 +271,10 @@ impl Agent {
             if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
                 let error =
                     HsaError::from_status_with_context(status, "Failed to iterate all agents");
-                log_error(&format!("Agent collection failed: {}", error));
+                log_error(&format!(
+                    "Agent collection failed: {}",
+                    error.full_message()
+                ));
                 return Err(error);
             }
         }
@@ -74,6 +283,43 @@ impl Agent {
         Ok(agents)
     }
 
+    /// Every agent of `device_type`, filtered during the runtime's agent
+    /// iteration rather than after collecting all of them.
+    pub fn find_all_of_type(device_type: DeviceType) -> Result<Vec<Self>> {
+        log_debug(&format!("Finding all {:?} agents...", device_type));
+
+        let mut filter = AgentFilter {
+            device_type: device_type.into(),
+            agents: Vec::new(),
+        };
+
+        unsafe {
+            let status = bindings::hsa_iterate_agents(
+                Some(collect_agents_of_type_callback),
+                &mut filter as *mut _ as *mut c_void,
+            );
+
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                let error = HsaError::from_status_with_context(
+                    status,
+                    &format!("Failed to iterate {:?} agents", device_type),
+                );
+                log_error(&format!(
+                    "Agent collection failed: {}",
+                    error.full_message()
+                ));
+                return Err(error);
+            }
+        }
+
+        log_info(&format!(
+            "Found {} {:?} agents",
+            filter.agents.len(),
+            device_type
+        ));
+        Ok(filter.agents)
+    }
+
     pub fn device_type(&self) -> Result<DeviceType> {
         let mut device_type = bindings::hsa_device_type_t_HSA_DEVICE_TYPE_CPU;
 
@@ -87,21 +333,17 @@ impl Agent {
             if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
                 let error =
                     HsaError::from_status_with_context(status, "Failed to get agent device type");
-                log_error(&format!("Device type query failed: {}", error));
+                log_error(&format!(
+                    "Device type query failed: {}",
+                    error.full_message()
+                ));
                 return Err(error);
             }
         }
 
-        let device_type = match device_type {
-            bindings::hsa_device_type_t_HSA_DEVICE_TYPE_CPU => DeviceType::Cpu,
-            bindings::hsa_device_type_t_HSA_DEVICE_TYPE_GPU => DeviceType::Gpu,
-            bindings::hsa_device_type_t_HSA_DEVICE_TYPE_DSP => DeviceType::Dsp,
-            bindings::hsa_device_type_t_HSA_DEVICE_TYPE_AIE => DeviceType::Aie,
-            _ => {
-                log_error(&format!("Unknown device type: {}", device_type));
-                return Err(HsaError::InvalidArgument("Unknown device type".to_string()));
-            }
-        };
+        let device_type = DeviceType::try_from(device_type).inspect_err(|error| {
+            log_error(&error.full_message());
+        })?;
 
         log_debug(&format!(
             "Agent 0x{:x} device type: {:?}",
@@ -170,6 +412,69 @@ impl Agent {
         Ok(vendor)
     }
 
+    pub fn profile(&self) -> Result<Profile> {
+        let mut profile = bindings::hsa_profile_t_HSA_PROFILE_BASE;
+
+        unsafe {
+            let status = bindings::hsa_agent_get_info(
+                self.handle,
+                bindings::hsa_agent_info_t_HSA_AGENT_INFO_PROFILE,
+                &mut profile as *mut _ as *mut c_void,
+            );
+
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                let error =
+                    HsaError::from_status_with_context(status, "Failed to get agent profile");
+                return Err(error);
+            }
+        }
+
+        let profile = Profile::from_raw(profile).ok_or_else(|| {
+            HsaError::InvalidArgument(format!("Unknown agent profile: {}", profile))
+        })?;
+
+        log_debug(&format!(
+            "Agent 0x{:x} profile: {:?}",
+            self.handle.handle, profile
+        ));
+        Ok(profile)
+    }
+
+    pub fn machine_model(&self) -> Result<MachineModel> {
+        let mut machine_model = bindings::hsa_machine_model_t_HSA_MACHINE_MODEL_LARGE;
+
+        unsafe {
+            let status = bindings::hsa_agent_get_info(
+                self.handle,
+                bindings::hsa_agent_info_t_HSA_AGENT_INFO_MACHINE_MODEL,
+                &mut machine_model as *mut _ as *mut c_void,
+            );
+
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                let error =
+                    HsaError::from_status_with_context(status, "Failed to get agent machine model");
+                return Err(error);
+            }
+        }
+
+        let machine_model = match machine_model {
+            bindings::hsa_machine_model_t_HSA_MACHINE_MODEL_SMALL => MachineModel::Small,
+            bindings::hsa_machine_model_t_HSA_MACHINE_MODEL_LARGE => MachineModel::Large,
+            other => {
+                return Err(HsaError::InvalidArgument(format!(
+                    "Unknown agent machine model: {}",
+                    other
+                )));
+            }
+        };
+
+        log_debug(&format!(
+            "Agent 0x{:x} machine model: {:?}",
+            self.handle.handle, machine_model
+        ));
+        Ok(machine_model)
+    }
+
     pub fn supports_kernel_dispatch(&self) -> Result<bool> {
         let mut feature = 0u32;
 
@@ -196,6 +501,550 @@ impl Agent {
         Ok(supports)
     }
 
+    /// Whether the agent can run cooperative (grid-synchronising) dispatches,
+    /// i.e. whether [`Queue::create_cooperative`](crate::Queue::create_cooperative)
+    /// will succeed on it.
+    pub fn supports_cooperative_dispatch(&self) -> Result<bool> {
+        let mut supported = false;
+
+        unsafe {
+            let status = bindings::hsa_agent_get_info(
+                self.handle,
+                bindings::hsa_amd_agent_info_s_HSA_AMD_AGENT_INFO_COOPERATIVE_QUEUES,
+                &mut supported as *mut _ as *mut c_void,
+            );
+
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                let error = HsaError::from_status_with_context(
+                    status,
+                    "Failed to query cooperative queue support",
+                );
+                return Err(error);
+            }
+        }
+
+        log_debug(&format!(
+            "Agent 0x{:x} supports cooperative queues: {}",
+            self.handle.handle, supported
+        ));
+        Ok(supported)
+    }
+
+    /// Whether the agent supports the HSA images extension, and the largest
+    /// images it can create.
+    pub fn image_support(&self) -> Result<ImageSupport> {
+        let mut extensions = [0u8; 128];
+
+        unsafe {
+            let status = bindings::hsa_agent_get_info(
+                self.handle,
+                bindings::hsa_agent_info_t_HSA_AGENT_INFO_EXTENSIONS,
+                extensions.as_mut_ptr() as *mut c_void,
+            );
+
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                return Err(HsaError::from_status_with_context(
+                    status,
+                    "Failed to get agent extensions",
+                ));
+            }
+        }
+
+        let images = bindings::hsa_extension_t_HSA_EXTENSION_IMAGES as usize;
+        if extensions[images / 8] & (1 << (images % 8)) == 0 {
+            log_debug(&format!(
+                "Agent 0x{:x} does not support images",
+                self.handle.handle
+            ));
+            return Ok(ImageSupport::default());
+        }
+
+        let mut max_1d = 0usize;
+        let mut max_2d = [0usize; 2];
+        let mut max_3d = [0usize; 3];
+
+        unsafe {
+            for (attribute, value) in [
+                (
+                    bindings::HSA_EXT_AGENT_INFO_IMAGE_1D_MAX_ELEMENTS,
+                    &mut max_1d as *mut _ as *mut c_void,
+                ),
+                (
+                    bindings::HSA_EXT_AGENT_INFO_IMAGE_2D_MAX_ELEMENTS,
+                    max_2d.as_mut_ptr() as *mut c_void,
+                ),
+                (
+                    bindings::HSA_EXT_AGENT_INFO_IMAGE_3D_MAX_ELEMENTS,
+                    max_3d.as_mut_ptr() as *mut c_void,
+                ),
+            ] {
+                let status = bindings::hsa_agent_get_info(
+                    self.handle,
+                    attribute as bindings::hsa_agent_info_t,
+                    value,
+                );
+
+                if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                    return Err(HsaError::from_status_with_context(
+                        status,
+                        "Failed to get image dimension limits",
+                    ));
+                }
+            }
+        }
+
+        let support = ImageSupport {
+            supported: true,
+            max_1d,
+            max_2d: (max_2d[0], max_2d[1]),
+            max_3d: (max_3d[0], max_3d[1], max_3d[2]),
+        };
+        log_debug(&format!(
+            "Agent 0x{:x} image support: {:?}",
+            self.handle.handle, support
+        ));
+        Ok(support)
+    }
+
+    /// Flush the host data path (HDP) cache of this GPU.
+    ///
+    /// Host writes to device-local memory through a CPU mapping (e.g. a
+    /// coarse-grained VRAM buffer on a large-BAR system) pass through the
+    /// HDP and can sit there unseen by the GPU. Call this after the host has
+    /// finished writing such memory and before telling the GPU to read it,
+    /// i.e. before dispatching the consuming kernel or storing to a signal a
+    /// running kernel polls. Writes to system memory, including
+    /// fine-grained host allocations, never go through the HDP and need no
+    /// flush.
+    pub fn flush_hdp(&self) -> Result<()> {
+        let mut flush = bindings::hsa_amd_hdp_flush_t::default();
+
+        unsafe {
+            let status = bindings::hsa_agent_get_info(
+                self.handle,
+                bindings::hsa_amd_agent_info_s_HSA_AMD_AGENT_INFO_HDP_FLUSH,
+                &mut flush as *mut _ as *mut c_void,
+            );
+
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                return Err(HsaError::from_status_with_context(
+                    status,
+                    "Failed to get HDP flush registers",
+                ));
+            }
+        }
+
+        if flush.HDP_MEM_FLUSH_CNTL.is_null() {
+            return Err(HsaError::Unsupported(format!(
+                "agent 0x{:x} does not expose an HDP flush register",
+                self.handle.handle
+            )));
+        }
+
+        unsafe {
+            ptr::write_volatile(flush.HDP_MEM_FLUSH_CNTL, 1);
+            // Read back so the posted MMIO write has reached the device
+            // before returning.
+            let _ = ptr::read_volatile(flush.HDP_MEM_FLUSH_CNTL);
+        }
+
+        log_debug(&format!("Agent 0x{:x} HDP flushed", self.handle.handle));
+        Ok(())
+    }
+
+    /// Maximum scratch (private segment) memory, in bytes, the runtime will
+    /// back for this agent across all queues of the process.
+    pub fn scratch_limit_max(&self) -> Result<usize> {
+        let mut limit = 0usize;
+
+        unsafe {
+            let status = bindings::hsa_agent_get_info(
+                self.handle,
+                bindings::hsa_amd_agent_info_s_HSA_AMD_AGENT_INFO_SCRATCH_LIMIT_MAX,
+                &mut limit as *mut _ as *mut c_void,
+            );
+
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                let error =
+                    HsaError::from_status_with_context(status, "Failed to get scratch limit");
+                return Err(error);
+            }
+        }
+
+        log_debug(&format!(
+            "Agent 0x{:x} scratch limit: {} bytes",
+            self.handle.handle, limit
+        ));
+        Ok(limit)
+    }
+
+    /// Number of SIMDs in each compute unit.
+    pub fn simds_per_cu(&self) -> Result<u32> {
+        self.amd_info_u32(
+            bindings::hsa_amd_agent_info_s_HSA_AMD_AGENT_INFO_NUM_SIMDS_PER_CU,
+            "SIMDs per CU",
+        )
+    }
+
+    /// Number of shader engines.
+    pub fn shader_engines(&self) -> Result<u32> {
+        self.amd_info_u32(
+            bindings::hsa_amd_agent_info_s_HSA_AMD_AGENT_INFO_NUM_SHADER_ENGINES,
+            "shader engines",
+        )
+    }
+
+    /// Number of shader arrays in each shader engine.
+    pub fn shader_arrays_per_se(&self) -> Result<u32> {
+        self.amd_info_u32(
+            bindings::hsa_amd_agent_info_s_HSA_AMD_AGENT_INFO_NUM_SHADER_ARRAYS_PER_SE,
+            "shader arrays per SE",
+        )
+    }
+
+    /// PCI device ID of the ASIC.
+    pub fn chip_id(&self) -> Result<u32> {
+        self.amd_info_u32(
+            bindings::hsa_amd_agent_info_s_HSA_AMD_AGENT_INFO_CHIP_ID,
+            "chip ID",
+        )
+    }
+
+    /// Silicon revision of the ASIC, which tells apart steppings sold under
+    /// the same product name.
+    pub fn asic_revision(&self) -> Result<u32> {
+        self.amd_info_u32(
+            bindings::hsa_amd_agent_info_s_HSA_AMD_AGENT_INFO_ASIC_REVISION,
+            "ASIC revision",
+        )
+    }
+
+    /// Peak engine clock in MHz.
+    pub fn max_clock_frequency(&self) -> Result<u32> {
+        self.amd_info_u32(
+            bindings::hsa_amd_agent_info_s_HSA_AMD_AGENT_INFO_MAX_CLOCK_FREQUENCY,
+            "max clock frequency (MHz)",
+        )
+    }
+
+    /// Number of hardware address watch points the GPU provides, 0 if it has
+    /// none. Read from the capability bits of the agent's KFD topology node.
+    ///
+    /// Only the query is offered: setting watch points is not part of the
+    /// HSA runtime API, and debuggers program them through the KFD debug
+    /// interface instead (e.g. with rocm-dbgapi).
+    pub fn max_watch_points(&self) -> Result<u32> {
+        let capability = self.kfd_node_property("capability")?;
+        let watch_points = if capability & KFD_CAP_WATCH_POINTS_SUPPORTED == 0 {
+            0
+        } else {
+            1 << ((capability & KFD_CAP_WATCH_POINTS_TOTALBITS_MASK)
+                >> KFD_CAP_WATCH_POINTS_TOTALBITS_SHIFT)
+        };
+
+        log_debug(&format!(
+            "Agent 0x{:x} watch points: {}",
+            self.handle.handle, watch_points
+        ));
+        Ok(watch_points)
+    }
+
+    /// Current engine clock in MHz. Well below [`Agent::max_clock_frequency`]
+    /// under load means the GPU is power or thermal throttled.
+    ///
+    /// The runtime does not report this, so it is read from the amdgpu
+    /// driver's sysfs files; it fails with `Unsupported` where those are not
+    /// available.
+    pub fn current_clock_frequency(&self) -> Result<u32> {
+        let path = self.sysfs_device_dir()?.join("pp_dpm_sclk");
+        let levels = read_sysfs(&path)?;
+
+        // One line per DPM level, e.g. "1: 1800Mhz *", the active one starred.
+        let clock = levels
+            .lines()
+            .find(|line| line.trim_end().ends_with('*'))
+            .and_then(|line| line.split_whitespace().nth(1))
+            .and_then(|mhz| {
+                mhz.trim_end_matches(|c: char| c.is_ascii_alphabetic())
+                    .parse()
+                    .ok()
+            })
+            .ok_or_else(|| {
+                HsaError::Unsupported(format!("unrecognized format in {}", path.display()))
+            })?;
+
+        log_debug(&format!(
+            "Agent 0x{:x} current clock: {} MHz",
+            self.handle.handle, clock
+        ));
+        Ok(clock)
+    }
+
+    /// Share of time, in percent, the GPU has recently been busy, as reported
+    /// by the amdgpu driver. Fails with `Unsupported` where it is not
+    /// available.
+    pub fn busy_percent(&self) -> Result<u32> {
+        let path = self.sysfs_device_dir()?.join("gpu_busy_percent");
+        let busy = read_sysfs(&path)?.trim().parse().map_err(|_| {
+            HsaError::Unsupported(format!("unrecognized format in {}", path.display()))
+        })?;
+
+        log_debug(&format!("Agent 0x{:x} busy: {}%", self.handle.handle, busy));
+        Ok(busy)
+    }
+
+    /// The amdgpu device directory in sysfs, found through the agent's KFD
+    /// topology node.
+    fn sysfs_device_dir(&self) -> Result<PathBuf> {
+        let minor = self.kfd_node_property("drm_render_minor")?;
+        Ok(PathBuf::from(format!(
+            "/sys/class/drm/renderD{}/device",
+            minor
+        )))
+    }
+
+    /// A numeric property of the agent's KFD topology node.
+    fn kfd_node_property(&self, name: &str) -> Result<u64> {
+        let node = self.amd_info_u32(
+            bindings::hsa_amd_agent_info_s_HSA_AMD_AGENT_INFO_DRIVER_NODE_ID,
+            "driver node id",
+        )?;
+        let properties = read_sysfs(Path::new(&format!(
+            "/sys/class/kfd/kfd/topology/nodes/{}/properties",
+            node
+        )))?;
+
+        properties
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(' '))
+            .and_then(|value| value.trim().parse::<u64>().ok())
+            .ok_or_else(|| {
+                HsaError::Unsupported(format!("KFD node {} has no {} property", node, name))
+            })
+    }
+
+    fn amd_info_u32(&self, attribute: bindings::hsa_agent_info_t, what: &str) -> Result<u32> {
+        let mut value = 0u32;
+
+        unsafe {
+            let status = bindings::hsa_agent_get_info(
+                self.handle,
+                attribute,
+                &mut value as *mut _ as *mut c_void,
+            );
+
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                return Err(HsaError::from_status_with_context(
+                    status,
+                    &format!("Failed to get {}", what),
+                ));
+            }
+        }
+
+        log_debug(&format!(
+            "Agent 0x{:x} {}: {}",
+            self.handle.handle, what, value
+        ));
+        Ok(value)
+    }
+
+    /// Number of work-items in a wavefront.
+    pub fn wavefront_size(&self) -> Result<u32> {
+        let mut wavefront_size = 0u32;
+
+        unsafe {
+            let status = bindings::hsa_agent_get_info(
+                self.handle,
+                bindings::hsa_agent_info_t_HSA_AGENT_INFO_WAVEFRONT_SIZE,
+                &mut wavefront_size as *mut _ as *mut c_void,
+            );
+
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                let error =
+                    HsaError::from_status_with_context(status, "Failed to get wavefront size");
+                return Err(error);
+            }
+        }
+
+        log_debug(&format!(
+            "Agent 0x{:x} wavefront size: {}",
+            self.handle.handle, wavefront_size
+        ));
+        Ok(wavefront_size)
+    }
+
+    /// Maximum number of work-items in a workgroup.
+    pub fn workgroup_max_size(&self) -> Result<u32> {
+        let mut max_size = 0u32;
+
+        unsafe {
+            let status = bindings::hsa_agent_get_info(
+                self.handle,
+                bindings::hsa_agent_info_t_HSA_AGENT_INFO_WORKGROUP_MAX_SIZE,
+                &mut max_size as *mut _ as *mut c_void,
+            );
+
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                let error =
+                    HsaError::from_status_with_context(status, "Failed to get workgroup max size");
+                return Err(error);
+            }
+        }
+
+        log_debug(&format!(
+            "Agent 0x{:x} workgroup max size: {}",
+            self.handle.handle, max_size
+        ));
+        Ok(max_size)
+    }
+
+    /// Maximum number of work-items in each dimension of a workgroup.
+    pub fn workgroup_max_dim(&self) -> Result<Dim3> {
+        let mut max_dim = [0u16; 3];
+
+        unsafe {
+            let status = bindings::hsa_agent_get_info(
+                self.handle,
+                bindings::hsa_agent_info_t_HSA_AGENT_INFO_WORKGROUP_MAX_DIM,
+                max_dim.as_mut_ptr() as *mut c_void,
+            );
+
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                let error = HsaError::from_status_with_context(
+                    status,
+                    "Failed to get workgroup max dimensions",
+                );
+                return Err(error);
+            }
+        }
+
+        let max_dim = Dim3::new(max_dim[0] as u32, max_dim[1] as u32, max_dim[2] as u32);
+        log_debug(&format!(
+            "Agent 0x{:x} workgroup max dimensions: {:?}",
+            self.handle.handle, max_dim
+        ));
+        Ok(max_dim)
+    }
+
+    /// A reasonable default workgroup shape for a `dims`-dimensional problem
+    /// (1 to 3): up to 256 work-items, never fewer than one wavefront, spread
+    /// evenly over the used dimensions, e.g. 16x16x1 for two dimensions.
+    ///
+    /// This is a portable starting point, not a tuned value; the best shape
+    /// depends on the kernel's register, LDS and memory access pattern.
+    pub fn recommended_workgroup_size(&self, dims: u8) -> Result<Dim3> {
+        if !(1..=3).contains(&dims) {
+            return Err(HsaError::InvalidArgument(format!(
+                "Workgroup dimension count must be 1, 2 or 3, got {}",
+                dims
+            )));
+        }
+
+        let max_size = self.workgroup_max_size()?;
+        let budget = AUTO_WORKGROUP_BUDGET
+            .max(self.wavefront_size()?)
+            .min(max_size);
+        if budget == 0 {
+            return Err(HsaError::InvalidArgument(
+                "Agent reports a maximum workgroup size of 0".to_string(),
+            ));
+        }
+        // Round down to a power of two.
+        let budget = 1 << budget.ilog2();
+
+        let extent = |dim: u8| if dim < dims { u32::MAX } else { 1 };
+        let problem = Dim3::new(extent(0), extent(1), extent(2));
+        let workgroup = auto_workgroup_size(problem, budget, self.workgroup_max_dim()?);
+
+        log_debug(&format!(
+            "Agent 0x{:x} recommended {}D workgroup: {}x{}x{}",
+            self.handle.handle, dims, workgroup.x, workgroup.y, workgroup.z
+        ));
+        Ok(workgroup)
+    }
+
+    /// Maximum number of work-items in each dimension of a grid.
+    pub fn grid_max_dim(&self) -> Result<Dim3> {
+        let mut max_dim = bindings::hsa_dim3_t { x: 0, y: 0, z: 0 };
+
+        unsafe {
+            let status = bindings::hsa_agent_get_info(
+                self.handle,
+                bindings::hsa_agent_info_t_HSA_AGENT_INFO_GRID_MAX_DIM,
+                &mut max_dim as *mut _ as *mut c_void,
+            );
+
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                let error =
+                    HsaError::from_status_with_context(status, "Failed to get grid max dimensions");
+                return Err(error);
+            }
+        }
+
+        let max_dim = Dim3::new(max_dim.x, max_dim.y, max_dim.z);
+        log_debug(&format!(
+            "Agent 0x{:x} grid max dimensions: {:?}",
+            self.handle.handle, max_dim
+        ));
+        Ok(max_dim)
+    }
+
+    /// Maximum total number of work-items in a grid.
+    pub fn grid_max_size(&self) -> Result<u32> {
+        let mut max_size = 0u32;
+
+        unsafe {
+            let status = bindings::hsa_agent_get_info(
+                self.handle,
+                bindings::hsa_agent_info_t_HSA_AGENT_INFO_GRID_MAX_SIZE,
+                &mut max_size as *mut _ as *mut c_void,
+            );
+
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                let error =
+                    HsaError::from_status_with_context(status, "Failed to get grid max size");
+                return Err(error);
+            }
+        }
+
+        log_debug(&format!(
+            "Agent 0x{:x} grid max size: {}",
+            self.handle.handle, max_size
+        ));
+        Ok(max_size)
+    }
+
+    /// Check launch dimensions against this agent's limits, failing with an
+    /// `InvalidArgument` error that names the first limit violated.
+    ///
+    /// The workgroup is checked against `workgroup_max_dim` and
+    /// `workgroup_max_size`, the grid against `grid_max_dim` and
+    /// `grid_max_size`. Dimensions of 0 are rejected as well. Dispatches
+    /// check against limits their queue cached when first used, so this
+    /// queries the agent only when called directly.
+    pub fn validate_launch(&self, grid: Dim3, workgroup: Dim3) -> Result<()> {
+        self.launch_limits()?.check(grid, workgroup)
+    }
+
+    /// Everything dispatch validation needs to know about this agent. The
+    /// values never change, so queues query them once and keep them.
+    pub(crate) fn launch_limits(&self) -> Result<LaunchLimits> {
+        let scratch_per_work_item = match (self.scratch_limit_max(), self.wavefront_size()) {
+            (Ok(limit), Ok(wavefront_size)) => Some(limit as u64 / wavefront_size.max(1) as u64),
+            _ => None,
+        };
+
+        Ok(LaunchLimits {
+            agent: *self,
+            device_type: self.device_type()?,
+            workgroup_max_dim: self.workgroup_max_dim()?,
+            workgroup_max_size: self.workgroup_max_size()?,
+            grid_max_dim: self.grid_max_dim()?,
+            grid_max_size: self.grid_max_size()?,
+            scratch_per_work_item,
+        })
+    }
+
     pub fn get_queue_max_size(&self) -> Result<u32> {
         let mut max_size = 0u32;
 
@@ -244,29 +1093,96 @@ impl Agent {
         Ok(min_size)
     }
 
+    /// Regions of `peer` that this agent can access, either by default or
+    /// once access is granted (see [`Memory::enable_peer_access`]).
+    ///
+    /// [`Memory::enable_peer_access`]: crate::Memory::enable_peer_access
+    pub fn accessible_regions(&self, peer: &Agent) -> Result<Vec<MemoryRegion>> {
+        let mut accessible = Vec::new();
+
+        for region in peer.iterate_memory_regions()? {
+            if region.pool().accessible_by(self)? != PoolAccess::Never {
+                accessible.push(region);
+            }
+        }
+
+        log_debug(&format!(
+            "Agent 0x{:x} can access {} regions of agent 0x{:x}",
+            self.handle.handle,
+            accessible.len(),
+            peer.handle.handle
+        ));
+        Ok(accessible)
+    }
+
+    pub fn iterate_memory_pools(&self) -> Result<Vec<MemoryPool>> {
+        log_debug(&format!(
+            "Iterating memory pools for agent 0x{:x}",
+            self.handle.handle
+        ));
+
+        let mut collector = PoolCollector {
+            agent: *self,
+            pools: Vec::new(),
+        };
+
+        unsafe {
+            let status = bindings::hsa_amd_agent_iterate_memory_pools(
+                self.handle,
+                Some(collect_pools_callback),
+                &mut collector as *mut _ as *mut c_void,
+            );
+
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                let error =
+                    HsaError::from_status_with_context(status, "Failed to iterate memory pools");
+                log_error(&format!(
+                    "Memory pool iteration failed: {}",
+                    error.full_message()
+                ));
+                return Err(error);
+            }
+        }
+
+        log_debug(&format!(
+            "Found {} memory pools for agent 0x{:x}",
+            collector.pools.len(),
+            self.handle.handle
+        ));
+        Ok(collector.pools)
+    }
+
     pub fn iterate_memory_regions(&self) -> Result<Vec<MemoryRegion>> {
         log_debug(&format!(
             "Iterating memory regions for agent 0x{:x}",
             self.handle.handle
         ));
 
-        let mut regions: Vec<MemoryRegion> = Vec::new();
+        let mut collector = RegionCollector {
+            agent: *self,
+            regions: Vec::new(),
+        };
 
         unsafe {
             let status = bindings::hsa_agent_iterate_regions(
                 self.handle,
                 Some(collect_regions_callback),
-                &mut regions as *mut _ as *mut c_void,
+                &mut collector as *mut _ as *mut c_void,
             );
 
             if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
                 let error =
                     HsaError::from_status_with_context(status, "Failed to iterate memory regions");
-                log_error(&format!("Memory region iteration failed: {}", error));
+                log_error(&format!(
+                    "Memory region iteration failed: {}",
+                    error.full_message()
+                ));
                 return Err(error);
             }
         }
 
+        let regions = collector.regions;
+
         log_debug(&format!(
             "Found {} memory regions for agent 0x{:x}",
             regions.len(),
@@ -325,6 +1241,60 @@ impl Agent {
         Ok(regions)
     }
 
+    /// The agent's caches, sorted by ascending level.
+    pub fn iterate_caches(&self) -> Result<Vec<CacheInfo>> {
+        let mut handles: Vec<bindings::hsa_cache_t> = Vec::new();
+
+        unsafe {
+            let status = bindings::hsa_agent_iterate_caches(
+                self.handle,
+                Some(collect_caches_callback),
+                &mut handles as *mut _ as *mut c_void,
+            );
+
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                let error = HsaError::from_status_with_context(status, "Failed to iterate caches");
+                log_error(&format!("Cache iteration failed: {}", error.full_message()));
+                return Err(error);
+            }
+        }
+
+        let mut caches = handles
+            .into_iter()
+            .map(cache_info)
+            .collect::<Result<Vec<_>>>()?;
+        caches.sort_by_key(|cache| cache.level);
+
+        log_debug(&format!(
+            "Found {} caches for agent 0x{:x}",
+            caches.len(),
+            self.handle.handle
+        ));
+        Ok(caches)
+    }
+
+    /// Query the agent's commonly needed properties, including its cache
+    /// hierarchy, in one call.
+    pub fn info(&self) -> Result<AgentInfo> {
+        Ok(AgentInfo {
+            name: self.get_name()?,
+            vendor_name: self.get_vendor_name()?,
+            device_type: self.device_type()?,
+            profile: self.profile()?,
+            machine_model: self.machine_model()?,
+            wavefront_size: self.wavefront_size()?,
+            workgroup_max_size: self.workgroup_max_size()?,
+            queue_max_size: self.get_queue_max_size()?,
+            cacheline_size: self
+                .amd_info_u32(
+                    bindings::hsa_amd_agent_info_s_HSA_AMD_AGENT_INFO_CACHELINE_SIZE,
+                    "cache line size",
+                )
+                .ok(),
+            caches: self.iterate_caches()?,
+        })
+    }
+
     pub fn print_info(&self) -> Result<()> {
         log_info(&format!(
             "Agent Information (Handle: 0x{:x}):",
@@ -334,47 +1304,125 @@ impl Agent {
         // Device type
         match self.device_type() {
             Ok(device_type) => log_info(&format!("  Device Type: {:?}", device_type)),
-            Err(e) => log_error(&format!("  Device Type: Error - {}", e)),
+            Err(e) => log_error(&format!("  Device Type: Error - {}", e.full_message())),
         }
 
         // Name
         match self.get_name() {
             Ok(name) => log_info(&format!("  Name: {}", name)),
-            Err(e) => log_error(&format!("  Name: Error - {}", e)),
+            Err(e) => log_error(&format!("  Name: Error - {}", e.full_message())),
         }
 
         // Vendor
         match self.get_vendor_name() {
             Ok(vendor) => log_info(&format!("  Vendor: {}", vendor)),
-            Err(e) => log_error(&format!("  Vendor: Error - {}", e)),
+            Err(e) => log_error(&format!("  Vendor: Error - {}", e.full_message())),
         }
 
         // Kernel dispatch support
         match self.supports_kernel_dispatch() {
             Ok(supports) => log_info(&format!("  Supports Kernel Dispatch: {}", supports)),
-            Err(e) => log_error(&format!("  Kernel Dispatch Support: Error - {}", e)),
+            Err(e) => log_error(&format!(
+                "  Kernel Dispatch Support: Error - {}",
+                e.full_message()
+            )),
         }
 
         // Queue sizes
         match (self.get_queue_min_size(), self.get_queue_max_size()) {
             (Ok(min), Ok(max)) => log_info(&format!("  Queue Size Range: {} - {}", min, max)),
-            (Err(e), _) | (_, Err(e)) => log_error(&format!("  Queue Size Range: Error - {}", e)),
+            (Err(e), _) | (_, Err(e)) => {
+                log_error(&format!("  Queue Size Range: Error - {}", e.full_message()))
+            }
         }
 
         // Memory regions
         match self.iterate_memory_regions() {
             Ok(regions) => log_info(&format!("  Memory Regions: {} found", regions.len())),
-            Err(e) => log_error(&format!("  Memory Regions: Error - {}", e)),
+            Err(e) => log_error(&format!("  Memory Regions: Error - {}", e.full_message())),
         }
 
         Ok(())
     }
 }
 
-unsafe extern "C" fn find_gpu_callback(
+/// Watch point bits of a KFD topology node's `capability` property.
+const KFD_CAP_WATCH_POINTS_SUPPORTED: u64 = 0x0000_0080;
+const KFD_CAP_WATCH_POINTS_TOTALBITS_MASK: u64 = 0x0000_0f00;
+const KFD_CAP_WATCH_POINTS_TOTALBITS_SHIFT: u64 = 8;
+
+fn read_sysfs(path: &Path) -> Result<String> {
+    std::fs::read_to_string(path)
+        .map_err(|e| HsaError::Unsupported(format!("cannot read {}: {}", path.display(), e)))
+}
+
+fn cache_info(cache: bindings::hsa_cache_t) -> Result<CacheInfo> {
+    let mut name_length: u32 = 0;
+    let mut level: u8 = 0;
+    let mut size: u32 = 0;
+
+    unsafe {
+        for (attribute, value, what) in [
+            (
+                bindings::hsa_cache_info_t_HSA_CACHE_INFO_NAME_LENGTH,
+                &mut name_length as *mut u32 as *mut c_void,
+                "name length",
+            ),
+            (
+                bindings::hsa_cache_info_t_HSA_CACHE_INFO_LEVEL,
+                &mut level as *mut u8 as *mut c_void,
+                "level",
+            ),
+            (
+                bindings::hsa_cache_info_t_HSA_CACHE_INFO_SIZE,
+                &mut size as *mut u32 as *mut c_void,
+                "size",
+            ),
+        ] {
+            let status = bindings::hsa_cache_get_info(cache, attribute, value);
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                return Err(HsaError::from_status_with_context(
+                    status,
+                    &format!("Failed to get cache {}", what),
+                ));
+            }
+        }
+    }
+
+    let mut name_buffer = vec![0u8; name_length as usize + 1];
+    unsafe {
+        let status = bindings::hsa_cache_get_info(
+            cache,
+            bindings::hsa_cache_info_t_HSA_CACHE_INFO_NAME,
+            name_buffer.as_mut_ptr() as *mut c_void,
+        );
+        if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+            return Err(HsaError::from_status_with_context(
+                status,
+                "Failed to get cache name",
+            ));
+        }
+    }
+    let name_end = name_buffer
+        .iter()
+        .position(|&c| c == 0)
+        .unwrap_or(name_buffer.len());
+
+    Ok(CacheInfo {
+        name: String::from_utf8_lossy(&name_buffer[..name_end]).to_string(),
+        level,
+        size,
+    })
+}
+
+struct AgentSearch {
+    device_type: bindings::hsa_device_type_t,
     agent: bindings::hsa_agent_t,
-    data: *mut c_void,
-) -> bindings::hsa_status_t {
+}
+
+fn raw_device_type(
+    agent: bindings::hsa_agent_t,
+) -> std::result::Result<bindings::hsa_device_type_t, bindings::hsa_status_t> {
     let mut device_type = bindings::hsa_device_type_t_HSA_DEVICE_TYPE_CPU;
     let status = unsafe {
         bindings::hsa_agent_get_info(
@@ -389,18 +1437,52 @@ unsafe extern "C" fn find_gpu_callback(
             "Failed to get device type for agent 0x{:x}",
             agent.handle
         ));
-        return status;
+        return Err(status);
     }
 
-    if device_type == bindings::hsa_device_type_t_HSA_DEVICE_TYPE_GPU {
-        let agent_ptr = data as *mut bindings::hsa_agent_t;
-        unsafe { *agent_ptr = agent };
+    Ok(device_type)
+}
+
+unsafe extern "C" fn find_agent_callback(
+    agent: bindings::hsa_agent_t,
+    data: *mut c_void,
+) -> bindings::hsa_status_t {
+    let search = unsafe { &mut *(data as *mut AgentSearch) };
+
+    let device_type = match raw_device_type(agent) {
+        Ok(device_type) => device_type,
+        Err(status) => return status,
+    };
+
+    if device_type == search.device_type {
+        search.agent = agent;
         return bindings::hsa_status_t_HSA_STATUS_INFO_BREAK;
     }
 
     bindings::hsa_status_t_HSA_STATUS_SUCCESS
 }
 
+struct AgentFilter {
+    device_type: bindings::hsa_device_type_t,
+    agents: Vec<Agent>,
+}
+
+unsafe extern "C" fn collect_agents_of_type_callback(
+    agent: bindings::hsa_agent_t,
+    data: *mut c_void,
+) -> bindings::hsa_status_t {
+    let filter = unsafe { &mut *(data as *mut AgentFilter) };
+
+    match raw_device_type(agent) {
+        Ok(device_type) if device_type == filter.device_type => {
+            filter.agents.push(Agent { handle: agent });
+            bindings::hsa_status_t_HSA_STATUS_SUCCESS
+        }
+        Ok(_) => bindings::hsa_status_t_HSA_STATUS_SUCCESS,
+        Err(status) => status,
+    }
+}
+
 unsafe extern "C" fn collect_all_agents_callback(
     agent: bindings::hsa_agent_t,
     data: *mut c_void,
@@ -410,11 +1492,45 @@ unsafe extern "C" fn collect_all_agents_callback(
     bindings::hsa_status_t_HSA_STATUS_SUCCESS
 }
 
+struct RegionCollector {
+    agent: Agent,
+    regions: Vec<MemoryRegion>,
+}
+
 unsafe extern "C" fn collect_regions_callback(
     region: bindings::hsa_region_t,
     data: *mut c_void,
 ) -> bindings::hsa_status_t {
-    let regions = unsafe { &mut *(data as *mut Vec<MemoryRegion>) };
-    regions.push(MemoryRegion { handle: region });
+    let collector = unsafe { &mut *(data as *mut RegionCollector) };
+    collector.regions.push(MemoryRegion {
+        handle: region,
+        agent: collector.agent,
+    });
+    bindings::hsa_status_t_HSA_STATUS_SUCCESS
+}
+
+struct PoolCollector {
+    agent: Agent,
+    pools: Vec<MemoryPool>,
+}
+
+unsafe extern "C" fn collect_pools_callback(
+    pool: bindings::hsa_amd_memory_pool_t,
+    data: *mut c_void,
+) -> bindings::hsa_status_t {
+    let collector = unsafe { &mut *(data as *mut PoolCollector) };
+    collector.pools.push(MemoryPool {
+        handle: pool,
+        agent: collector.agent,
+    });
+    bindings::hsa_status_t_HSA_STATUS_SUCCESS
+}
+
+unsafe extern "C" fn collect_caches_callback(
+    cache: bindings::hsa_cache_t,
+    data: *mut c_void,
+) -> bindings::hsa_status_t {
+    let caches = unsafe { &mut *(data as *mut Vec<bindings::hsa_cache_t>) };
+    caches.push(cache);
     bindings::hsa_status_t_HSA_STATUS_SUCCESS
 }
@@ -0,0 +1,276 @@
+//! Minimal read-only inspection of AMDGPU code object ELF files.
+//!
+//! Only the pieces the loader helpers need are decoded: the ELF64 header, the
+//! `PT_NOTE`/`SHT_NOTE` records and the symbol table. Malformed input yields
+//! `None` rather than an error so callers can fall back to letting the
+//! runtime decide.
+
+const ELF_MAGIC: &[u8; 4] = b"\x7fELF";
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+const ELF64_HEADER_SIZE: usize = 64;
+const EI_OSABI: usize = 7;
+const EI_ABIVERSION: usize = 8;
+const ELFOSABI_AMDGPU_HSA: u8 = 64;
+
+/// `e_machine` value of AMDGPU code objects.
+pub(crate) const EM_AMDGPU: u16 = 224;
+
+const PT_NOTE: u32 = 4;
+const SHT_NOTE: u32 = 7;
+const SHT_SYMTAB: u32 = 2;
+const SHT_DYNSYM: u32 = 11;
+
+const ELF64_SYM_SIZE: usize = 24;
+const STB_GLOBAL: u8 = 1;
+const STB_WEAK: u8 = 2;
+const STT_OBJECT: u8 = 1;
+const STT_AMDGPU_HSA_KERNEL: u8 = 10;
+const STV_HIDDEN: u8 = 2;
+
+/// Note type of the HSAIL properties note in code object v2 (owner "AMD").
+const NT_AMD_HSA_HSAIL: u32 = 2;
+
+/// Profile and rounding mode recorded by the v2 HSAIL note, using the HSA
+/// enumeration values.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct HsailProperties {
+    pub profile: u8,
+    pub default_float_round: u8,
+}
+
+pub(crate) struct Note<'a> {
+    pub name: &'a [u8],
+    pub note_type: u32,
+    pub desc: &'a [u8],
+}
+
+pub(crate) struct ElfFile<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> ElfFile<'a> {
+    /// Wrap `data` if it starts with a little-endian ELF64 header.
+    pub fn parse(data: &'a [u8]) -> Option<Self> {
+        if data.len() < ELF64_HEADER_SIZE
+            || &data[..4] != ELF_MAGIC
+            || data[4] != ELFCLASS64
+            || data[5] != ELFDATA2LSB
+        {
+            return None;
+        }
+        Some(Self { data })
+    }
+
+    pub fn machine(&self) -> u16 {
+        read_u16(self.data, 18).unwrap_or(0)
+    }
+
+    /// Code object version from `e_ident`. HSA code objects store it as the
+    /// ELF ABI version, where 0 means v2 and each later version adds one.
+    pub fn code_object_version(&self) -> Option<u32> {
+        if self.data[EI_OSABI] != ELFOSABI_AMDGPU_HSA {
+            return None;
+        }
+        Some(u32::from(self.data[EI_ABIVERSION]) + 2)
+    }
+
+    /// Every note record, taken from the program headers when present and
+    /// from the section headers otherwise.
+    pub fn notes(&self) -> Vec<Note<'a>> {
+        let mut notes = Vec::new();
+
+        for (offset, size) in self.note_ranges() {
+            let Some(end) = offset.checked_add(size) else {
+                continue;
+            };
+            if let Some(bytes) = self.data.get(offset..end) {
+                parse_notes(bytes, &mut notes);
+            }
+        }
+
+        notes
+    }
+
+    pub fn hsail_properties(&self) -> Option<HsailProperties> {
+        self.notes()
+            .into_iter()
+            .find(|note| note.note_type == NT_AMD_HSA_HSAIL && note.name == b"AMD")
+            .and_then(|note| {
+                // hsail_major (u32), hsail_minor (u32), profile (u8),
+                // machine_model (u8), default_float_round (u8)
+                let desc = note.desc;
+                if desc.len() < 11 {
+                    return None;
+                }
+                Some(HsailProperties {
+                    profile: desc[8],
+                    default_float_round: desc[10],
+                })
+            })
+    }
+
+    /// Number of symbols the runtime exposes once the object is loaded:
+    /// defined, non-hidden global or weak variables and kernels. v3+ objects
+    /// export kernels through their `.kd` descriptor objects, v2 objects
+    /// mark them with the AMDGPU kernel symbol type. `.dynsym` is used when
+    /// present, `.symtab` otherwise.
+    pub fn exported_symbol_count(&self) -> Option<usize> {
+        let shoff = read_u64(self.data, 40)? as usize;
+        let shentsize = read_u16(self.data, 58)? as usize;
+        let shnum = read_u16(self.data, 60)? as usize;
+
+        let section = |wanted: u32| {
+            (0..shnum).find_map(|i| {
+                let base = shoff.saturating_add(i.saturating_mul(shentsize));
+                if read_u32(self.data, base.saturating_add(4))? != wanted {
+                    return None;
+                }
+                Some((
+                    read_u64(self.data, base.saturating_add(24))? as usize,
+                    read_u64(self.data, base.saturating_add(32))? as usize,
+                ))
+            })
+        };
+        let (offset, size) = section(SHT_DYNSYM).or_else(|| section(SHT_SYMTAB))?;
+        let symbols = self.data.get(offset..offset.checked_add(size)?)?;
+
+        let count = symbols
+            .chunks_exact(ELF64_SYM_SIZE)
+            .filter(|sym| {
+                let (info, other) = (sym[4], sym[5]);
+                let shndx = u16::from_le_bytes([sym[6], sym[7]]);
+                let binding = info >> 4;
+                let kind = info & 0xf;
+                shndx != 0
+                    && (binding == STB_GLOBAL || binding == STB_WEAK)
+                    && other & 3 != STV_HIDDEN
+                    && (kind == STT_OBJECT || kind == STT_AMDGPU_HSA_KERNEL)
+            })
+            .count();
+        Some(count)
+    }
+
+    fn note_ranges(&self) -> Vec<(usize, usize)> {
+        let mut ranges = Vec::new();
+
+        let phoff = read_u64(self.data, 32).unwrap_or(0) as usize;
+        let phentsize = read_u16(self.data, 54).unwrap_or(0) as usize;
+        let phnum = read_u16(self.data, 56).unwrap_or(0) as usize;
+        for i in 0..phnum {
+            let base = phoff.saturating_add(i.saturating_mul(phentsize));
+            if read_u32(self.data, base) == Some(PT_NOTE)
+                && let (Some(offset), Some(size)) = (
+                    read_u64(self.data, base.saturating_add(8)),
+                    read_u64(self.data, base.saturating_add(32)),
+                )
+            {
+                ranges.push((offset as usize, size as usize));
+            }
+        }
+
+        if !ranges.is_empty() {
+            return ranges;
+        }
+
+        let shoff = read_u64(self.data, 40).unwrap_or(0) as usize;
+        let shentsize = read_u16(self.data, 58).unwrap_or(0) as usize;
+        let shnum = read_u16(self.data, 60).unwrap_or(0) as usize;
+        for i in 0..shnum {
+            let base = shoff.saturating_add(i.saturating_mul(shentsize));
+            if read_u32(self.data, base.saturating_add(4)) == Some(SHT_NOTE)
+                && let (Some(offset), Some(size)) = (
+                    read_u64(self.data, base.saturating_add(24)),
+                    read_u64(self.data, base.saturating_add(32)),
+                )
+            {
+                ranges.push((offset as usize, size as usize));
+            }
+        }
+
+        ranges
+    }
+}
+
+/// Check that `data` looks like an AMDGPU code object, describing what is
+/// wrong with it otherwise.
+pub(crate) fn check_amdgpu_elf(data: &[u8]) -> Result<(), String> {
+    if !data.starts_with(ELF_MAGIC) {
+        return Err("not an AMDGPU ELF: missing ELF magic".to_string());
+    }
+    let Some(elf) = ElfFile::parse(data) else {
+        return Err(format!(
+            "not an AMDGPU ELF: truncated or not a 64-bit little-endian ELF ({} bytes)",
+            data.len()
+        ));
+    };
+    if elf.machine() != EM_AMDGPU {
+        return Err(format!(
+            "not an AMDGPU ELF: machine type is {}, expected {}",
+            elf.machine(),
+            EM_AMDGPU
+        ));
+    }
+    Ok(())
+}
+
+/// Oldest code object version the ROCr loader accepts.
+pub(crate) const MIN_CODE_OBJECT_VERSION: u32 = 2;
+
+/// Newest code object version a ROCr runtime of the given library version
+/// can load, going by the release that introduced each version.
+pub(crate) fn max_code_object_version(major: u32, minor: u32) -> u32 {
+    match (major, minor) {
+        (0, _) | (1, 0..=2) => 3,
+        (1, 3..=4) => 4,
+        (1, 5..=13) => 5,
+        _ => 6,
+    }
+}
+
+fn parse_notes<'a>(mut bytes: &'a [u8], notes: &mut Vec<Note<'a>>) {
+    while bytes.len() >= 12 {
+        let namesz = read_u32(bytes, 0).unwrap_or(0) as usize;
+        let descsz = read_u32(bytes, 4).unwrap_or(0) as usize;
+        let note_type = read_u32(bytes, 8).unwrap_or(0);
+
+        let name_start = 12;
+        let desc_start = name_start + align4(namesz);
+        let next = desc_start + align4(descsz);
+        if next > bytes.len() {
+            return;
+        }
+
+        // The name is NUL terminated; strip the terminator for comparisons.
+        let mut name = &bytes[name_start..name_start + namesz];
+        if let Some((&0, rest)) = name.split_last() {
+            name = rest;
+        }
+
+        notes.push(Note {
+            name,
+            note_type,
+            desc: &bytes[desc_start..desc_start + descsz],
+        });
+        bytes = &bytes[next..];
+    }
+}
+
+fn align4(value: usize) -> usize {
+    (value + 3) & !3
+}
+
+pub(crate) fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset.checked_add(2)?)?;
+    Some(u16::from_le_bytes(bytes.try_into().ok()?))
+}
+
+pub(crate) fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_le_bytes(bytes.try_into().ok()?))
+}
+
+pub(crate) fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset.checked_add(8)?)?;
+    Some(u64::from_le_bytes(bytes.try_into().ok()?))
+}
@@ -1,19 +1,129 @@
 use crate::bindings;
-use crate::{Agent, HsaError, MemoryRegion, Queue, Result};
+use crate::error::{log_error, log_warning};
+use crate::{Agent, DeviceType, HsaError, Memory, MemoryRegion, Queue, Result};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
 
+/// How long [`HsaContext::recreate_queue`] waits for the old queue to drain.
+const QUEUE_DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// An initialized runtime together with one agent, its memory regions and
+/// a default queue.
+///
+/// # Thread safety
+///
+/// `HsaContext` is `Send` and `Sync`, so it can be put in an `Arc` and
+/// shared with a thread pool:
+///
+/// - The default queue and queues added with [`HsaContext::add_queue`] are
+///   `MULTI` queues. Any number of threads may dispatch to the same queue
+///   through `&Queue`; packet slots are claimed atomically.
+/// - Allocating from the regions and [`HsaContext::allocate_staging`] only
+///   need `&self`.
+/// - Changing the set of queues ([`HsaContext::add_queue`],
+///   [`HsaContext::recreate_queue`]) needs `&mut self`, so do it before
+///   sharing the context.
+///
+/// The runtime is reference counted by `hsa_init`/`hsa_shut_down`, and the
+/// context shuts down only its own reference when dropped, which with an
+/// `Arc` is when the last clone goes away.
 pub struct HsaContext {
     pub agent: Agent,
+    /// The default queue; see [`HsaContext::default_queue`].
     pub queue: Option<Queue>,
     pub kernarg_region: Option<MemoryRegion>,
     pub fine_grained_region: Option<MemoryRegion>,
     pub coarse_grained_region: Option<MemoryRegion>,
+    /// The first CPU agent in the system, used for host-side staging.
+    pub cpu_agent: Option<Agent>,
+    /// Fine-grained global region of `cpu_agent` that staging buffers are
+    /// allocated from.
+    pub staging_region: Option<MemoryRegion>,
+    /// Queues added with [`HsaContext::add_queue`].
+    queues: Vec<Queue>,
 }
 
 impl HsaContext {
     pub fn new() -> Result<Self> {
+        Self::new_for(DeviceType::Gpu)
+    }
+
+    /// Like [`HsaContext::new`], but gives up with
+    /// [`HsaError::InitializationFailed`] if runtime initialization and agent
+    /// discovery take longer than `timeout`, instead of hanging the caller.
+    ///
+    /// HSA calls cannot be cancelled, so on timeout the initialization keeps
+    /// running on a detached thread, which is leaked if it never returns. If
+    /// it does finish later, the context it built is dropped straight away.
+    pub fn new_with_timeout(timeout: Duration) -> Result<Self> {
+        let (sender, receiver) = mpsc::channel();
+        std::thread::Builder::new()
+            .name("hsa-init".to_string())
+            .spawn(move || {
+                // The receiver is gone after a timeout; the context is then
+                // dropped here, which shuts the runtime down again.
+                let _ = sender.send(Self::new());
+            })
+            .map_err(|e| {
+                HsaError::InitializationFailed(format!("cannot spawn init thread: {}", e))
+            })?;
+
+        match receiver.recv_timeout(timeout) {
+            Ok(result) => result,
+            Err(RecvTimeoutError::Timeout) => {
+                log_error(&format!(
+                    "HSA initialization did not complete within {:?}",
+                    timeout
+                ));
+                Err(HsaError::InitializationFailed("timed out".to_string()))
+            }
+            Err(RecvTimeoutError::Disconnected) => Err(HsaError::InitializationFailed(
+                "init thread panicked".to_string(),
+            )),
+        }
+    }
+
+    pub fn new_cpu() -> Result<Self> {
+        Self::new_for(DeviceType::Cpu)
+    }
+
+    /// Build a context around the first agent of `device_type`.
+    ///
+    /// GPU contexts require a coarse-grained global region and somewhere to
+    /// put kernel arguments (a kernarg region, or failing that a fine-grained
+    /// global region), and always get a queue. Other device types only pick
+    /// up the regions they expose, and get a queue only if the agent
+    /// supports kernel dispatch (the ROCm CPU agent does not, so `queue` is
+    /// `None` there).
+    pub fn new_for(device_type: DeviceType) -> Result<Self> {
+        crate::init()?;
+
+        let result = Agent::find(device_type).and_then(|agent| Self::build(agent, device_type));
+        if result.is_err() {
+            let _ = crate::shutdown();
+        }
+        result
+    }
+
+    /// Build a context around a specific agent, e.g. one picked with
+    /// [`Agent::find_all_of_type`].
+    ///
+    /// The agent is held to the same requirements as in
+    /// [`HsaContext::new_for`], so a GPU agent missing a region the context
+    /// promises is rejected here rather than when the region is first used.
+    pub fn from_agent(agent: Agent) -> Result<Self> {
         crate::init()?;
 
-        let agent = Agent::find_gpu()?;
+        let result = agent
+            .device_type()
+            .and_then(|device_type| Self::build(agent, device_type));
+        if result.is_err() {
+            let _ = crate::shutdown();
+        }
+        result
+    }
+
+    fn build(agent: Agent, device_type: DeviceType) -> Result<Self> {
         let regions = agent.iterate_memory_regions()?;
 
         let mut kernarg_region = None;
@@ -40,24 +150,148 @@ impl HsaContext {
             }
         }
 
-        let coarse_grained_region = coarse_grained_region.ok_or(HsaError::MemoryRegionNotFound)?;
-        let fine_grained_region = fine_grained_region.ok_or(HsaError::MemoryRegionNotFound)?;
+        let queue = if device_type == DeviceType::Gpu {
+            if kernarg_region.is_none() && fine_grained_region.is_none() {
+                return Err(HsaError::MemoryRegionNotFound(format!(
+                    "agent 0x{:x} has neither a kernarg region nor a fine-grained global region",
+                    agent.handle.handle
+                )));
+            }
+            if coarse_grained_region.is_none() {
+                return Err(HsaError::MemoryRegionNotFound(format!(
+                    "agent 0x{:x} has no coarse-grained global region",
+                    agent.handle.handle
+                )));
+            }
+            Some(Queue::create(&agent, 1024)?)
+        } else if agent.supports_kernel_dispatch()? {
+            Some(Queue::create(&agent, 1024)?)
+        } else {
+            None
+        };
 
-        let queue = Queue::create(&agent, 1024)?;
+        let (cpu_agent, staging_region) = if device_type == DeviceType::Cpu {
+            (Some(agent), fine_grained_region)
+        } else {
+            match Agent::find(DeviceType::Cpu) {
+                Ok(cpu) => (Some(cpu), Self::find_fine_grained_region(&cpu)?),
+                Err(_) => (None, None),
+            }
+        };
 
         Ok(Self {
             agent,
-            queue: Some(queue),
+            queue,
             kernarg_region,
-            fine_grained_region: Some(fine_grained_region),
-            coarse_grained_region: Some(coarse_grained_region),
+            fine_grained_region,
+            coarse_grained_region,
+            cpu_agent,
+            staging_region,
+            queues: Vec::new(),
         })
     }
+
+    /// Replace the context's queue with a new one of `size` packets, e.g. to
+    /// get more depth at runtime.
+    ///
+    /// The replacement is created first, so on failure the existing queue
+    /// is kept. The existing queue is then drained: this blocks until the
+    /// packet processor has consumed every packet submitted to it, for at
+    /// most 10 seconds. A queue that reported an error never consumes its
+    /// remaining packets and is replaced without waiting. If the drain times
+    /// out the existing queue is kept and an error returned. Kernels may
+    /// still be running once their packets are consumed, so wait on their
+    /// completion signals before relying on their results.
+    pub fn recreate_queue(&mut self, size: u32) -> Result<()> {
+        let replacement = Queue::create(&self.agent, size)?;
+
+        if let Some(queue) = &self.queue {
+            let start = Instant::now();
+            while queue.info().pending > 0 && !queue.has_error() {
+                if start.elapsed() > QUEUE_DRAIN_TIMEOUT {
+                    return Err(HsaError::ExecutionFailed(format!(
+                        "queue 0x{:x} did not drain within {:?}; {} packets still pending",
+                        queue.get_id(),
+                        QUEUE_DRAIN_TIMEOUT,
+                        queue.info().pending
+                    )));
+                }
+                std::thread::yield_now();
+            }
+            if queue.has_error() {
+                log_warning(&format!(
+                    "Replacing failed queue 0x{:x} without draining it",
+                    queue.get_id()
+                ));
+            }
+        }
+
+        self.queue = Some(replacement);
+        Ok(())
+    }
+
+    /// The queue created with the context, the same one as the `queue`
+    /// field.
+    pub fn default_queue(&self) -> Option<&Queue> {
+        self.queue.as_ref()
+    }
+
+    /// Create another queue of `size` packets on the context's agent, e.g.
+    /// to keep transfers and compute on separate queues. Returns its index
+    /// for [`HsaContext::queue`].
+    pub fn add_queue(&mut self, size: u32) -> Result<usize> {
+        self.queues.push(Queue::create(&self.agent, size)?);
+        Ok(self.queues.len() - 1)
+    }
+
+    /// A queue added with [`HsaContext::add_queue`]. The default queue is
+    /// not part of this list.
+    pub fn queue(&self, index: usize) -> Option<&Queue> {
+        self.queues.get(index)
+    }
+
+    /// Number of queues added with [`HsaContext::add_queue`].
+    pub fn queue_count(&self) -> usize {
+        self.queues.len()
+    }
+
+    fn find_fine_grained_region(agent: &Agent) -> Result<Option<MemoryRegion>> {
+        for region in agent.iterate_memory_regions()? {
+            if region.segment()? == bindings::hsa_region_segment_t_HSA_REGION_SEGMENT_GLOBAL
+                && region.global_flags()?
+                    & bindings::hsa_region_global_flag_t_HSA_REGION_GLOBAL_FLAG_FINE_GRAINED
+                    != 0
+            {
+                return Ok(Some(region));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Allocate a host-side staging buffer from the CPU agent's fine-grained
+    /// region, the usual source or destination for transfers to and from a
+    /// discrete GPU.
+    pub fn allocate_staging(&self, size: usize) -> Result<Memory> {
+        let region = self.staging_region.ok_or_else(|| {
+            HsaError::MemoryRegionNotFound("fine-grained CPU staging region".to_string())
+        })?;
+        let memory = region.allocate(size)?;
+        memory.allow_access(&[self.agent])?;
+        Ok(memory)
+    }
 }
 
 impl Drop for HsaContext {
     fn drop(&mut self) {
         self.queue.take();
+        self.queues.clear();
         let _ = crate::shutdown();
     }
 }
+
+// Sharing a context across threads is part of its contract; fail the build
+// if a field ever stops being `Send + Sync`.
+const _: () = {
+    fn assert_send_sync<T: Send + Sync>() {}
+    let _ = assert_send_sync::<HsaContext>;
+};
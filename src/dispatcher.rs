@@ -0,0 +1,213 @@
+use crate::error::{log_debug, log_info};
+use crate::queue::{clear_packet, publish_packet};
+use crate::{HsaError, KernelDispatch, Queue, Result, Signal};
+
+/// Launches kernels on several queues with as little skew as possible.
+///
+/// HSA has no way to ring several doorbells at once, so the dispatcher does
+/// everything it can up front: every packet is written into its queue first,
+/// then all headers are published, and only then are the doorbells rung back
+/// to back. The gap between the first and last kernel starting is reduced to
+/// the doorbell writes themselves.
+#[derive(Default)]
+pub struct MultiQueueDispatcher<'a> {
+    entries: Vec<(&'a Queue, KernelDispatch, &'a Signal)>,
+}
+
+impl<'a> MultiQueueDispatcher<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue `dispatch` for launch on `queue`, signalling `completion` when
+    /// it finishes. Several dispatches may target the same queue; they are
+    /// submitted in the order they were added.
+    pub fn add(
+        &mut self,
+        queue: &'a Queue,
+        dispatch: KernelDispatch,
+        completion: &'a Signal,
+    ) -> &mut Self {
+        self.entries.push((queue, dispatch, completion));
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Submit every added dispatch.
+    ///
+    /// All dispatches are checked before any packet is written, so an error
+    /// here means nothing was submitted. Fails if more dispatches were added
+    /// for one queue than its ring has slots.
+    pub fn launch(self) -> Result<()> {
+        let entries: Vec<_> = self
+            .entries
+            .iter()
+            .map(|(queue, dispatch, completion)| (*queue, dispatch, *completion))
+            .collect();
+        submit_all(&entries)
+    }
+}
+
+/// Buffers dispatches for one queue and submits them highest priority first.
+///
+/// This is a software scheduler on top of a single queue, unrelated to
+/// hardware queue priorities: it only reorders the dispatches buffered since
+/// the last [`PriorityDispatcher::flush`]. Work from different flush windows
+/// is never reordered, and once submitted the packet processor may run
+/// kernels concurrently regardless of priority. Dispatches of equal priority
+/// keep the order they were pushed in.
+pub struct PriorityDispatcher<'a> {
+    queue: &'a Queue,
+    entries: Vec<(i32, KernelDispatch, &'a Signal)>,
+}
+
+impl<'a> PriorityDispatcher<'a> {
+    pub fn new(queue: &'a Queue) -> Self {
+        Self {
+            queue,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Buffer `dispatch` with `priority`; higher values are submitted first.
+    pub fn push(
+        &mut self,
+        priority: i32,
+        dispatch: KernelDispatch,
+        completion: &'a Signal,
+    ) -> &mut Self {
+        self.entries.push((priority, dispatch, completion));
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Submit every buffered dispatch in priority order.
+    ///
+    /// All dispatches are checked before any packet is written. On error
+    /// nothing is submitted and the dispatches stay buffered. More
+    /// dispatches than the queue has slots are submitted in several
+    /// batches, each published before the next is claimed.
+    pub fn flush(&mut self) -> Result<()> {
+        self.entries
+            .sort_by_key(|&(priority, _, _)| std::cmp::Reverse(priority));
+
+        let entries: Vec<_> = self
+            .entries
+            .iter()
+            .map(|(_, dispatch, completion)| (self.queue, dispatch, *completion))
+            .collect();
+        check_all(&entries)?;
+        log_debug(&format!(
+            "Flushing {} dispatches in priority order",
+            entries.len()
+        ));
+
+        let size = (self.queue.get_size() as usize).max(1);
+        for batch in entries.chunks(size) {
+            submit_checked(batch, &[(self.queue, batch.len() as u64)]);
+        }
+
+        self.entries.clear();
+        Ok(())
+    }
+}
+
+/// Check every entry, then write all packets, publish all headers and ring
+/// all doorbells, in the order of `entries`.
+///
+/// The packets for each queue are claimed in one go and stay unpublished
+/// until all of them are written, so a queue cannot be given more entries
+/// than its ring holds: the packet processor would wait on this batch's own
+/// unpublished slots while this waits for it to free more. Such batches are
+/// rejected before anything is written.
+fn submit_all(entries: &[(&Queue, &KernelDispatch, &Signal)]) -> Result<()> {
+    check_all(entries)?;
+
+    let mut groups: Vec<(&Queue, u64)> = Vec::new();
+    for (queue, _, _) in entries {
+        match groups.iter_mut().find(|(q, _)| std::ptr::eq(*q, *queue)) {
+            Some((_, count)) => *count += 1,
+            None => groups.push((queue, 1)),
+        }
+    }
+    for &(queue, count) in &groups {
+        let size = queue.get_size() as u64;
+        if count > size {
+            return Err(HsaError::InvalidArgument(format!(
+                "Cannot submit {} dispatches at once to queue 0x{:x} of size {}",
+                count,
+                queue.get_id(),
+                size
+            )));
+        }
+    }
+
+    submit_checked(entries, &groups);
+    Ok(())
+}
+
+/// Run the per-dispatch checks on every entry.
+fn check_all(entries: &[(&Queue, &KernelDispatch, &Signal)]) -> Result<()> {
+    for (queue, dispatch, _) in entries {
+        dispatch.check_agent(queue)?;
+        queue.check_kernel_dispatch()?;
+        dispatch.check_scratch(queue)?;
+    }
+    Ok(())
+}
+
+/// Submit already checked `entries`. `groups` lists every queue in
+/// `entries` with its number of entries, none larger than the queue.
+fn submit_checked(entries: &[(&Queue, &KernelDispatch, &Signal)], groups: &[(&Queue, u64)]) {
+    // Next free index and last used index of each group's claimed range.
+    let mut ranges: Vec<(u64, u64)> = groups
+        .iter()
+        .map(|&(queue, count)| {
+            let first = queue.claim_slots(count);
+            (first, first + count - 1)
+        })
+        .collect();
+
+    let mut packets = Vec::with_capacity(entries.len());
+    for (queue, dispatch, completion) in entries {
+        let group = groups
+            .iter()
+            .position(|(q, _)| std::ptr::eq(*q, *queue))
+            .expect("every queue has a group");
+        let index = ranges[group].0;
+        ranges[group].0 += 1;
+
+        let packet = queue.packet_slot(index);
+        clear_packet(packet);
+        dispatch.fill_packet(packet, completion.handle());
+        packets.push(packet);
+    }
+
+    for ((_, dispatch, _), &packet) in entries.iter().zip(&packets) {
+        publish_packet(packet, dispatch.header());
+    }
+
+    for (&(queue, _), &(_, last)) in groups.iter().zip(&ranges) {
+        queue.ring_doorbell(last);
+    }
+
+    log_info(&format!(
+        "Launched {} dispatches across {} queues",
+        entries.len(),
+        groups.len()
+    ));
+}
@@ -1,19 +1,20 @@
 use crate::bindings;
 use std::ffi::CStr;
 use std::os::raw::c_char;
+use std::sync::OnceLock;
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, HsaError>;
 
 #[derive(Debug, Error)]
 pub enum HsaError {
-    #[error("HSA initialization failed")]
-    InitializationFailed,
+    #[error("HSA initialization failed: {0}")]
+    InitializationFailed(String),
 
     #[error("HSA shutdown failed")]
     ShutdownFailed,
 
-    #[error("No GPU agent found")]
+    #[error("No matching agent found")]
     AgentNotFound,
 
     #[error("Queue creation failed: {0}")]
@@ -40,8 +41,8 @@ pub enum HsaError {
     #[error("Kernel execution failed: {0}")]
     ExecutionFailed(String),
 
-    #[error("Required memory region not found")]
-    MemoryRegionNotFound,
+    #[error("Required memory region not found: {0}")]
+    MemoryRegionNotFound(String),
 
     #[error("Signal operation failed: {0}")]
     SignalOperationFailed(String),
@@ -91,11 +92,40 @@ pub enum HsaError {
     #[error("Fatal HSA error: {0}")]
     Fatal(String),
 
+    #[error("Invalid packet format: {0}")]
+    InvalidPacketFormat(String),
+
+    #[error("Invalid index: {0}")]
+    InvalidIndex(String),
+
+    #[error("HSA exception: {0}")]
+    Exception(String),
+
+    /// No packet slot was free in the queue. Not fatal: the submission can
+    /// be retried once the packet processor has drained some packets.
+    #[error("Queue full: {0}")]
+    QueueFull(String),
+
+    #[error("Unsupported: {0}")]
+    Unsupported(String),
+
     #[error("HSA error {status}: {description}")]
     HsaStatus { status: u32, description: String },
 
     #[error("String conversion error")]
     StringConversionError,
+
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// An error annotated with [`HsaError::context`]. The original error is
+    /// available through `Error::source` and [`HsaError::root_cause`].
+    #[error("{context}")]
+    WithContext {
+        context: String,
+        #[source]
+        source: Box<HsaError>,
+    },
 }
 
 impl HsaError {
@@ -156,6 +186,13 @@ impl HsaError {
             bindings::hsa_status_t_HSA_STATUS_ERROR_INVALID_ISA_NAME => {
                 Self::InvalidIsa(description)
             }
+            bindings::hsa_status_t_HSA_STATUS_ERROR_INVALID_PACKET_FORMAT => {
+                Self::InvalidPacketFormat(description)
+            }
+            bindings::hsa_status_t_HSA_STATUS_ERROR_INVALID_INDEX => {
+                Self::InvalidIndex(description)
+            }
+            bindings::hsa_status_t_HSA_STATUS_ERROR_EXCEPTION => Self::Exception(description),
             bindings::hsa_status_t_HSA_STATUS_ERROR_FATAL => Self::Fatal(description),
             _ => Self::HsaStatus {
                 status,
@@ -164,37 +201,56 @@ impl HsaError {
         }
     }
 
+    /// The error [`HsaError::from_status`] returns, wrapped in `context`.
+    /// Match on [`HsaError::root_cause`] to see the status variant, and use
+    /// [`HsaError::full_message`] to print the whole chain.
     pub fn from_status_with_context(status: bindings::hsa_status_t, context: &str) -> Self {
-        let mut error = Self::from_status(status);
-
-        // Add context to the error message
-        match &mut error {
-            Self::InvalidArgument(msg)
-            | Self::QueueCreationFailed(msg)
-            | Self::InvalidAllocation(msg)
-            | Self::InvalidAgent(msg)
-            | Self::InvalidRegion(msg)
-            | Self::OutOfResources(msg)
-            | Self::NotInitialized(msg)
-            | Self::InvalidCodeObject(msg)
-            | Self::InvalidExecutable(msg)
-            | Self::FrozenExecutable(msg)
-            | Self::InvalidSymbolName(msg)
-            | Self::VariableAlreadyDefined(msg)
-            | Self::VariableUndefined(msg)
-            | Self::IncompatibleArguments(msg)
-            | Self::InvalidIsa(msg)
-            | Self::Fatal(msg) => {
-                *msg = format!("{}: {}", context, msg);
-            }
-            Self::HsaStatus { description, .. } => {
-                *description = format!("{}: {}", context, description);
-            }
-            _ => {}
+        Self::from_status(status).context(context)
+    }
+
+    /// Wrap this error so that it becomes the `source` of an error
+    /// displaying `context`. Match on [`HsaError::root_cause`] to see the
+    /// original variant through any number of such layers.
+    pub fn context(self, context: impl Into<String>) -> Self {
+        Self::WithContext {
+            context: context.into(),
+            source: Box::new(self),
         }
+    }
 
+    /// The innermost error, with all context layers removed.
+    pub fn root_cause(&self) -> &HsaError {
+        let mut error = self;
+        while let Self::WithContext { source, .. } = error {
+            error = source;
+        }
         error
     }
+
+    /// This error and all of its causes on one line, separated by `": "`,
+    /// for places that need a flat string such as log lines.
+    pub fn full_message(&self) -> String {
+        let mut message = self.to_string();
+        let mut source = std::error::Error::source(self);
+        while let Some(cause) = source {
+            message.push_str(": ");
+            message.push_str(&cause.to_string());
+            source = cause.source();
+        }
+        message
+    }
+}
+
+/// The runtime's description of a raw `hsa_status_t` value, e.g. one
+/// received from other HSA code across an FFI boundary.
+pub fn describe_status(status: u32) -> String {
+    get_status_string(status)
+}
+
+/// Translate a raw `hsa_status_t` value into the matching [`HsaError`]
+/// variant; the same mapping as [`HsaError::from_status`].
+pub fn status_to_error(status: u32) -> HsaError {
+    HsaError::from_status(status)
 }
 
 fn get_status_string(status: bindings::hsa_status_t) -> String {
@@ -214,20 +270,50 @@ fn get_status_string(status: bindings::hsa_status_t) -> String {
 }
 
 // Logging utilities
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warning,
+    Error,
+}
+
+pub type LogCallback = Box<dyn Fn(LogLevel, &str) + Send + Sync>;
+
+static LOG_CALLBACK: OnceLock<LogCallback> = OnceLock::new();
+
+/// Route the crate's diagnostics to `callback` instead of stderr.
+///
+/// The callback can only be installed once per process; later calls return
+/// an error and leave the first callback in place. Debug messages are still
+/// only emitted when `HSA_DEBUG` is set.
+pub fn set_log_callback(callback: LogCallback) -> Result<()> {
+    LOG_CALLBACK.set(callback).map_err(|_| {
+        HsaError::InvalidArgument("A log callback has already been installed".to_string())
+    })
+}
+
+fn emit(level: LogLevel, tag: &str, message: &str) {
+    match LOG_CALLBACK.get() {
+        Some(callback) => callback(level, message),
+        None => eprintln!("[HSA {}] {}", tag, message),
+    }
+}
+
 pub fn log_info(message: &str) {
-    eprintln!("[HSA INFO] {}", message);
+    emit(LogLevel::Info, "INFO", message);
 }
 
 pub fn log_warning(message: &str) {
-    eprintln!("[HSA WARN] {}", message);
+    emit(LogLevel::Warning, "WARN", message);
 }
 
 pub fn log_error(message: &str) {
-    eprintln!("[HSA ERROR] {}", message);
+    emit(LogLevel::Error, "ERROR", message);
 }
 
 pub fn log_debug(message: &str) {
     if std::env::var("HSA_DEBUG").is_ok() {
-        eprintln!("[HSA DEBUG] {}", message);
+        emit(LogLevel::Debug, "DEBUG", message);
     }
 }
@@ -1,26 +1,243 @@
 use crate::Queue;
 use crate::bindings;
-use crate::error::{log_debug, log_error, log_info};
-use crate::{Agent, HsaError, Result, Signal};
-use std::ffi::CString;
-use std::os::raw::c_void;
+use crate::code_object::{
+    ElfFile, MIN_CODE_OBJECT_VERSION, check_amdgpu_elf, max_code_object_version,
+};
+use crate::error::{log_debug, log_error, log_info, log_warning};
+use crate::queue::{clear_packet, publish_packet, write_packet_body};
+use crate::{Agent, DeviceType, HsaError, Result, Signal};
+use std::ffi::{CStr, CString};
+use std::ops::RangeInclusive;
+use std::os::raw::{c_char, c_void};
 use std::ptr;
+use std::sync::Arc;
 
 pub struct Executable {
     handle: bindings::hsa_executable_t,
-    code_object_reader: Option<bindings::hsa_code_object_reader_t>,
+    /// Readers of every loaded code object, destroyed after the executable.
+    code_object_readers: Vec<bindings::hsa_code_object_reader_t>,
+    /// Readers passed to `load_program_code_object`, kept alive for as long
+    /// as the executable references them.
+    program_readers: Vec<CodeObjectReader>,
+}
+
+/// A code object reader over an owned copy of a code object's bytes.
+///
+/// Cloning is cheap and shares the underlying reader, which is destroyed once
+/// the last clone and every executable it was loaded into are gone.
+#[derive(Clone)]
+pub struct CodeObjectReader {
+    inner: Arc<ReaderHandle>,
+}
+
+struct ReaderHandle {
+    handle: bindings::hsa_code_object_reader_t,
+    // The runtime reads from this buffer for the lifetime of the reader
+    _code_object: Box<[u8]>,
+}
+
+impl CodeObjectReader {
+    pub fn from_bytes(code_object: &[u8]) -> Result<Self> {
+        if code_object.is_empty() {
+            return Err(HsaError::InvalidArgument(
+                "Code object is empty".to_string(),
+            ));
+        }
+
+        check_amdgpu_elf(code_object).map_err(HsaError::InvalidCodeObject)?;
+        Executable::warn_on_code_object_version(code_object);
+
+        let code_object: Box<[u8]> = code_object.into();
+        let mut reader = bindings::hsa_code_object_reader_t { handle: 0 };
+
+        unsafe {
+            let status = bindings::hsa_code_object_reader_create_from_memory(
+                code_object.as_ptr() as *const c_void,
+                code_object.len(),
+                &mut reader,
+            );
+
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                let error = HsaError::from_status_with_context(
+                    status,
+                    "Failed to create code object reader from memory",
+                );
+                return Err(HsaError::CodeObjectReaderFailed(error.full_message()));
+            }
+        }
+
+        log_debug(&format!(
+            "Created code object reader with handle: 0x{:x}",
+            reader.handle
+        ));
+
+        Ok(CodeObjectReader {
+            inner: Arc::new(ReaderHandle {
+                handle: reader,
+                _code_object: code_object,
+            }),
+        })
+    }
+
+    pub(crate) fn raw(&self) -> bindings::hsa_code_object_reader_t {
+        self.inner.handle
+    }
+}
+
+impl Drop for ReaderHandle {
+    fn drop(&mut self) {
+        let status = unsafe { bindings::hsa_code_object_reader_destroy(self.handle) };
+        if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+            let error =
+                HsaError::from_status_with_context(status, "Failed to destroy code object reader");
+            log_error(&error.full_message());
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Profile {
+    Base,
+    Full,
+}
+
+impl Profile {
+    pub(crate) fn raw(self) -> bindings::hsa_profile_t {
+        match self {
+            Profile::Base => bindings::hsa_profile_t_HSA_PROFILE_BASE,
+            Profile::Full => bindings::hsa_profile_t_HSA_PROFILE_FULL,
+        }
+    }
+
+    pub(crate) fn from_raw(profile: bindings::hsa_profile_t) -> Option<Self> {
+        match profile {
+            bindings::hsa_profile_t_HSA_PROFILE_BASE => Some(Profile::Base),
+            bindings::hsa_profile_t_HSA_PROFILE_FULL => Some(Profile::Full),
+            _ => None,
+        }
+    }
+}
+
+/// Default floating-point rounding mode an executable is created with. It
+/// must match the mode the code objects loaded into it were finalized with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FloatRoundingMode {
+    /// Accept code objects finalized with either mode.
+    Default,
+    /// Round towards zero.
+    Zero,
+    /// Round to nearest even.
+    Near,
+}
+
+impl FloatRoundingMode {
+    pub(crate) fn raw(self) -> bindings::hsa_default_float_rounding_mode_t {
+        match self {
+            FloatRoundingMode::Default => {
+                bindings::hsa_default_float_rounding_mode_t_HSA_DEFAULT_FLOAT_ROUNDING_MODE_DEFAULT
+            }
+            FloatRoundingMode::Zero => {
+                bindings::hsa_default_float_rounding_mode_t_HSA_DEFAULT_FLOAT_ROUNDING_MODE_ZERO
+            }
+            FloatRoundingMode::Near => {
+                bindings::hsa_default_float_rounding_mode_t_HSA_DEFAULT_FLOAT_ROUNDING_MODE_NEAR
+            }
+        }
+    }
+}
+
+/// What an executable symbol refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Variable,
+    Kernel,
+    /// A device function whose address can be passed to kernels, e.g. as a
+    /// function pointer kernel argument.
+    IndirectFunction,
+}
+
+impl SymbolKind {
+    pub(crate) fn from_raw(kind: bindings::hsa_symbol_kind_t) -> Option<Self> {
+        match kind {
+            bindings::hsa_symbol_kind_t_HSA_SYMBOL_KIND_VARIABLE => Some(SymbolKind::Variable),
+            bindings::hsa_symbol_kind_t_HSA_SYMBOL_KIND_KERNEL => Some(SymbolKind::Kernel),
+            bindings::hsa_symbol_kind_t_HSA_SYMBOL_KIND_INDIRECT_FUNCTION => {
+                Some(SymbolKind::IndirectFunction)
+            }
+            _ => None,
+        }
+    }
 }
 
 impl Executable {
     pub fn create() -> Result<Self> {
-        log_debug("Creating HSA executable");
+        Self::create_raw(
+            bindings::hsa_profile_t_HSA_PROFILE_FULL,
+            bindings::hsa_default_float_rounding_mode_t_HSA_DEFAULT_FLOAT_ROUNDING_MODE_NEAR,
+        )
+    }
+
+    pub fn create_with_profile(profile: Profile) -> Result<Self> {
+        Self::create_raw(
+            profile.raw(),
+            bindings::hsa_default_float_rounding_mode_t_HSA_DEFAULT_FLOAT_ROUNDING_MODE_NEAR,
+        )
+    }
+
+    /// Create an executable with an explicit profile and default rounding
+    /// mode, for code objects finalized with a non-default rounding mode.
+    pub fn create_with(profile: Profile, rounding: FloatRoundingMode) -> Result<Self> {
+        Self::create_raw(profile.raw(), rounding.raw())
+    }
+
+    /// Create an executable whose profile and default rounding mode match
+    /// `code_object`.
+    ///
+    /// Code object v2 records both in its HSAIL note, which is used when
+    /// present. Newer code objects carry no such note; for those the agent's
+    /// own profile is used together with the `DEFAULT` rounding mode, which
+    /// the loader accepts for any code object.
+    pub fn create_auto(agent: &Agent, code_object: &[u8]) -> Result<Self> {
+        let note = ElfFile::parse(code_object).and_then(|elf| elf.hsail_properties());
+
+        let (profile, rounding) = match note {
+            Some(note) => {
+                log_debug(&format!(
+                    "Code object HSAIL note: profile {}, default float rounding {}",
+                    note.profile, note.default_float_round
+                ));
+                (
+                    note.profile as bindings::hsa_profile_t,
+                    note.default_float_round as bindings::hsa_default_float_rounding_mode_t,
+                )
+            }
+            None => {
+                log_debug("Code object has no HSAIL note, using the agent profile");
+                (
+                    agent.profile()?.raw(),
+                    bindings::hsa_default_float_rounding_mode_t_HSA_DEFAULT_FLOAT_ROUNDING_MODE_DEFAULT,
+                )
+            }
+        };
+
+        Self::create_raw(profile, rounding)
+    }
+
+    fn create_raw(
+        profile: bindings::hsa_profile_t,
+        rounding: bindings::hsa_default_float_rounding_mode_t,
+    ) -> Result<Self> {
+        log_debug(&format!(
+            "Creating HSA executable (profile {}, default float rounding {})",
+            profile, rounding
+        ));
 
         let mut executable = bindings::hsa_executable_t { handle: 0 };
 
         unsafe {
             let status = bindings::hsa_executable_create_alt(
-                bindings::hsa_profile_t_HSA_PROFILE_FULL,
-                bindings::hsa_default_float_rounding_mode_t_HSA_DEFAULT_FLOAT_ROUNDING_MODE_NEAR,
+                profile,
+                rounding,
                 ptr::null(),
                 &mut executable,
             );
@@ -28,7 +245,10 @@ impl Executable {
             if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
                 let error =
                     HsaError::from_status_with_context(status, "Failed to create executable");
-                log_error(&format!("Executable creation failed: {}", error));
+                log_error(&format!(
+                    "Executable creation failed: {}",
+                    error.full_message()
+                ));
                 return Err(error);
             }
         }
@@ -40,11 +260,23 @@ impl Executable {
 
         Ok(Executable {
             handle: executable,
-            code_object_reader: None,
+            code_object_readers: Vec::new(),
+            program_readers: Vec::new(),
         })
     }
 
-    pub fn load_code_object(&mut self, agent: &Agent, code_object: &[u8]) -> Result<()> {
+    /// Load `code_object` for `agent`, returning how many symbols it adds to
+    /// the executable.
+    ///
+    /// The count is read from the code object's ELF symbol table rather than
+    /// by iterating the executable, so it is cheap enough to check after
+    /// every load. It is `None` if the code object has no readable symbol
+    /// table, which is distinct from `Some(0)`, a table without symbols.
+    ///
+    /// If the runtime rejects the code object and its version is outside
+    /// [`Executable::supported_code_object_versions`], the error is
+    /// `InvalidCodeObject` naming the version instead of the runtime's status.
+    pub fn load_code_object(&mut self, agent: &Agent, code_object: &[u8]) -> Result<Option<usize>> {
         log_info(&format!(
             "Loading code object ({} bytes) for agent 0x{:x}",
             code_object.len(),
@@ -58,6 +290,11 @@ impl Executable {
             ));
         }
 
+        // Catch truncated or foreign files before the runtime reports a bare
+        // INVALID_CODE_OBJECT
+        check_amdgpu_elf(code_object).map_err(HsaError::InvalidCodeObject)?;
+        Self::warn_on_code_object_version(code_object);
+
         // Create code object reader from memory
         let mut reader = bindings::hsa_code_object_reader_t { handle: 0 };
 
@@ -74,8 +311,11 @@ impl Executable {
                     status,
                     "Failed to create code object reader from memory",
                 );
-                log_error(&format!("Code object reader creation failed: {}", error));
-                return Err(HsaError::CodeObjectReaderFailed(error.to_string()));
+                log_error(&format!(
+                    "Code object reader creation failed: {}",
+                    error.full_message()
+                ));
+                return Err(HsaError::CodeObjectReaderFailed(error.full_message()));
             }
 
             log_debug(&format!(
@@ -104,29 +344,38 @@ impl Executable {
                     load_status,
                     "Failed to load agent code object",
                 );
-                log_error(&format!("Agent code object load failed: {}", error));
+                log_error(&format!(
+                    "Agent code object load failed: {}",
+                    error.full_message()
+                ));
+
+                // The version estimate is too rough to reject up front, but
+                // explains a failed load better than the generic status
+                if let Some(version_error) = Self::unsupported_code_object_version(code_object) {
+                    return Err(version_error);
+                }
 
                 // Provide additional context for common errors
                 let detailed_error = match load_status {
                     bindings::hsa_status_t_HSA_STATUS_ERROR_INCOMPATIBLE_ARGUMENTS => {
                         format!(
                             "{}\n  Possible causes:\n  - Code object ISA incompatible with agent\n  - Machine model mismatch\n  - Profile mismatch\n  - Floating-point mode mismatch",
-                            error
+                            error.full_message()
                         )
                     }
                     bindings::hsa_status_t_HSA_STATUS_ERROR_INVALID_CODE_OBJECT => {
                         format!(
                             "{}\n  Possible causes:\n  - Corrupted code object\n  - Invalid file format\n  - Unsupported code object version",
-                            error
+                            error.full_message()
                         )
                     }
                     bindings::hsa_status_t_HSA_STATUS_ERROR_OUT_OF_RESOURCES => {
                         format!(
                             "{}\n  Possible causes:\n  - Insufficient GPU memory\n  - Too many loaded code objects",
-                            error
+                            error.full_message()
                         )
                     }
-                    _ => error.to_string(),
+                    _ => error.full_message(),
                 };
 
                 return Err(HsaError::CodeObjectLoadFailed(detailed_error));
@@ -138,32 +387,280 @@ impl Executable {
             ));
         }
 
-        self.code_object_reader = Some(reader);
+        self.code_object_readers.push(reader);
+
+        let symbol_count = ElfFile::parse(code_object).and_then(|elf| elf.exported_symbol_count());
+        match symbol_count {
+            Some(count) => log_debug(&format!("Code object contributed {} symbols", count)),
+            None => log_debug("Code object symbol table unreadable, symbol count unknown"),
+        }
+        Ok(symbol_count)
+    }
+
+    /// Load a program-scoped code object, one that is not tied to a single
+    /// agent. The executable keeps `reader` alive until it is destroyed.
+    pub fn load_program_code_object(&mut self, reader: &CodeObjectReader) -> Result<()> {
+        log_info(&format!(
+            "Loading program code object (reader: 0x{:x})",
+            reader.raw().handle
+        ));
+
+        let mut loaded_code_object = bindings::hsa_loaded_code_object_t { handle: 0 };
+
+        unsafe {
+            let status = bindings::hsa_executable_load_program_code_object(
+                self.handle,
+                reader.raw(),
+                ptr::null(),
+                &mut loaded_code_object,
+            );
+
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                let error = HsaError::from_status_with_context(
+                    status,
+                    "Failed to load program code object",
+                );
+                log_error(&format!(
+                    "Program code object load failed: {}",
+                    error.full_message()
+                ));
+                return Err(HsaError::CodeObjectLoadFailed(error.full_message()));
+            }
+        }
+
+        log_info(&format!(
+            "Successfully loaded program code object (handle: 0x{:x})",
+            loaded_code_object.handle
+        ));
+
+        self.program_readers.push(reader.clone());
         Ok(())
     }
 
+    /// Total bytes of device memory occupied by the executable's loaded code
+    /// objects, as reported by the AMD loader extension.
+    pub fn memory_footprint(&self) -> Result<usize> {
+        let loader = amd_loader_table()?;
+        let (Some(iterate), Some(get_info)) = (
+            loader.hsa_ven_amd_loader_executable_iterate_loaded_code_objects,
+            loader.hsa_ven_amd_loader_loaded_code_object_get_info,
+        ) else {
+            return Err(HsaError::Unsupported(
+                "AMD loader extension does not report loaded code objects".to_string(),
+            ));
+        };
+
+        let mut loaded_code_objects: Vec<bindings::hsa_loaded_code_object_t> = Vec::new();
+        unsafe {
+            let status = iterate(
+                self.handle,
+                Some(collect_loaded_code_objects_callback),
+                &mut loaded_code_objects as *mut _ as *mut c_void,
+            );
+
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                return Err(HsaError::from_status_with_context(
+                    status,
+                    "Failed to iterate loaded code objects",
+                ));
+            }
+        }
+
+        let mut total = 0usize;
+        for loaded_code_object in &loaded_code_objects {
+            let mut load_size = 0u64;
+            unsafe {
+                let status = get_info(
+                    *loaded_code_object,
+                    bindings::hsa_ven_amd_loader_loaded_code_object_info_t_HSA_VEN_AMD_LOADER_LOADED_CODE_OBJECT_INFO_LOAD_SIZE,
+                    &mut load_size as *mut _ as *mut c_void,
+                );
+
+                if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                    return Err(HsaError::from_status_with_context(
+                        status,
+                        "Failed to get loaded code object size",
+                    ));
+                }
+            }
+            total += load_size as usize;
+        }
+
+        log_debug(&format!(
+            "Executable 0x{:x}: {} loaded code objects occupy {} bytes",
+            self.handle.handle,
+            loaded_code_objects.len(),
+            total
+        ));
+        Ok(total)
+    }
+
+    /// Range of code object versions the loaded runtime is expected to
+    /// accept, estimated from the ROCr build version. Vendor builds and
+    /// backports may accept more, so treat this as information rather than
+    /// a hard limit.
+    pub fn supported_code_object_versions() -> Result<RangeInclusive<u32>> {
+        let mut version: *const c_char = ptr::null();
+        let version = unsafe {
+            let status = bindings::hsa_system_get_info(
+                bindings::hsa_system_info_t_HSA_AMD_SYSTEM_INFO_BUILD_VERSION,
+                &mut version as *mut _ as *mut c_void,
+            );
+
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                return Err(HsaError::from_status_with_context(
+                    status,
+                    "Failed to get runtime build version",
+                ));
+            }
+            if version.is_null() {
+                return Err(HsaError::StringConversionError);
+            }
+            CStr::from_ptr(version)
+                .to_str()
+                .map_err(|_| HsaError::StringConversionError)?
+        };
+
+        let mut parts = version.split(|c: char| !c.is_ascii_digit());
+        let mut next = || parts.next().and_then(|part| part.parse::<u32>().ok());
+        let (Some(major), Some(minor)) = (next(), next()) else {
+            return Err(HsaError::InvalidArgument(format!(
+                "Unrecognized runtime build version: '{}'",
+                version
+            )));
+        };
+
+        let max = max_code_object_version(major, minor);
+        log_debug(&format!(
+            "Runtime {} supports code object v{} to v{}",
+            version, MIN_CODE_OBJECT_VERSION, max
+        ));
+        Ok(MIN_CODE_OBJECT_VERSION..=max)
+    }
+
+    /// Warn about code objects whose version the runtime probably cannot
+    /// load. The range is only an estimate from the runtime's version, so
+    /// the load itself still decides; if it fails,
+    /// [`Executable::load_code_object`] reports the version as the cause.
+    fn warn_on_code_object_version(code_object: &[u8]) {
+        if let Some(error) = Self::unsupported_code_object_version(code_object) {
+            log_warning(&format!("{}; loading it anyway", error));
+        }
+    }
+
+    /// `InvalidCodeObject` naming the code object's version if it is outside
+    /// [`Executable::supported_code_object_versions`], `None` if it is inside
+    /// or either version cannot be determined.
+    fn unsupported_code_object_version(code_object: &[u8]) -> Option<HsaError> {
+        let version = ElfFile::parse(code_object).and_then(|elf| elf.code_object_version())?;
+        let supported = match Self::supported_code_object_versions() {
+            Ok(supported) => supported,
+            Err(e) => {
+                log_debug(&format!(
+                    "Skipping code object version check: {}",
+                    e.full_message()
+                ));
+                return None;
+            }
+        };
+
+        if version > *supported.end() {
+            Some(HsaError::InvalidCodeObject(format!(
+                "code object v{} not supported by runtime (max v{})",
+                version,
+                supported.end()
+            )))
+        } else if version < *supported.start() {
+            Some(HsaError::InvalidCodeObject(format!(
+                "code object v{} not supported by runtime (min v{})",
+                version,
+                supported.start()
+            )))
+        } else {
+            None
+        }
+    }
+
+    /// Destroy the executable and its code object readers, reporting
+    /// failures that dropping it would only log.
+    ///
+    /// Kernels keep running from the executable's code, so only call this
+    /// (or drop the executable) once every dispatch of its kernels has
+    /// completed.
+    pub fn unload(mut self) -> Result<()> {
+        log_debug("Unloading executable");
+        self.destroy()
+    }
+
+    /// Destroy the executable first and then the readers its code objects
+    /// were loaded from. Safe to call again; later calls do nothing.
+    fn destroy(&mut self) -> Result<()> {
+        let mut result = Ok(());
+
+        if self.handle.handle != 0 {
+            let status = unsafe { bindings::hsa_executable_destroy(self.handle) };
+            self.handle.handle = 0;
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                result = Err(HsaError::from_status_with_context(
+                    status,
+                    "Failed to destroy executable",
+                ));
+            }
+        }
+
+        for reader in self.code_object_readers.drain(..) {
+            let status = unsafe { bindings::hsa_code_object_reader_destroy(reader) };
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS && result.is_ok() {
+                result = Err(HsaError::from_status_with_context(
+                    status,
+                    "Failed to destroy code object reader",
+                ));
+            }
+        }
+        self.program_readers.clear();
+
+        result
+    }
+
     pub fn freeze(&self) -> Result<()> {
         log_debug("Freezing executable");
+        self.freeze_raw(ptr::null())
+    }
+
+    /// Freeze the executable, passing `options` through to the runtime as the
+    /// vendor-specific options string of `hsa_executable_freeze`.
+    pub fn freeze_with_options(&self, options: &str) -> Result<()> {
+        log_debug(&format!("Freezing executable with options: '{}'", options));
 
+        let c_options = CString::new(options).map_err(|_| {
+            HsaError::InvalidArgument(format!("Invalid freeze options: '{}'", options))
+        })?;
+        self.freeze_raw(c_options.as_ptr())
+    }
+
+    fn freeze_raw(&self, options: *const c_char) -> Result<()> {
         unsafe {
-            let status = bindings::hsa_executable_freeze(self.handle, ptr::null());
+            let status = bindings::hsa_executable_freeze(self.handle, options);
             if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
                 let error =
                     HsaError::from_status_with_context(status, "Failed to freeze executable");
-                log_error(&format!("Executable freeze failed: {}", error));
+                log_error(&format!(
+                    "Executable freeze failed: {}",
+                    error.full_message()
+                ));
 
                 // Provide additional context for common freeze errors
                 let detailed_error = match status {
                     bindings::hsa_status_t_HSA_STATUS_ERROR_VARIABLE_UNDEFINED => {
                         format!(
                             "{}\n  One or more variables are undefined. All external variables must be defined before freezing.",
-                            error
+                            error.full_message()
                         )
                     }
                     bindings::hsa_status_t_HSA_STATUS_ERROR_FROZEN_EXECUTABLE => {
-                        format!("{}\n  Executable is already frozen.", error)
+                        format!("{}\n  Executable is already frozen.", error.full_message())
                     }
-                    _ => error.to_string(),
+                    _ => error.full_message(),
                 };
 
                 return Err(HsaError::ExecutableFreezeFailed(detailed_error));
@@ -174,48 +671,118 @@ impl Executable {
         Ok(())
     }
 
+    /// Look up a kernel by its source-level name, trying the spellings
+    /// compilers commonly emit: `name`, `name.kd` and
+    /// `__device_kernel__name`, in that order, and then any C++ mangled name
+    /// of a function called `name` (`_Z<len>name...`), whatever its
+    /// parameter types. Misses along the way are not logged; only a final
+    /// `KernelNotFound` is.
+    pub fn find_kernel(&self, base_name: &str, agent: &Agent) -> Result<KernelSymbol> {
+        let candidates = [
+            base_name.to_string(),
+            format!("{}.kd", base_name),
+            format!("__device_kernel__{}", base_name),
+        ];
+
+        for candidate in &candidates {
+            if let Ok(symbol) = self.lookup_symbol(candidate, agent) {
+                log_info(&format!(
+                    "Kernel '{}' found as symbol '{}'",
+                    base_name, candidate
+                ));
+                return Ok(symbol);
+            }
+        }
+
+        let mangled_prefix = format!("_Z{}{}", base_name.len(), base_name);
+        let mangled = self
+            .list_symbols(agent)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|name| name.starts_with(&mangled_prefix));
+        for candidate in mangled {
+            if let Ok(symbol) = self.lookup_symbol(&candidate, agent) {
+                log_info(&format!(
+                    "Kernel '{}' found as mangled symbol '{}'",
+                    base_name, candidate
+                ));
+                return Ok(symbol);
+            }
+        }
+
+        let error = HsaError::KernelNotFound(format!(
+            "No kernel symbol for '{}', tried: {}, {}*",
+            base_name,
+            candidates.join(", "),
+            mangled_prefix
+        ));
+        log_error(&error.to_string());
+        Err(error)
+    }
+
     pub fn get_kernel_symbol(&self, name: &str, agent: &Agent) -> Result<KernelSymbol> {
         log_debug(&format!("Looking for kernel symbol: '{}'", name));
 
+        match self.lookup_symbol(name, agent) {
+            Ok(symbol) => {
+                log_debug(&format!(
+                    "Found kernel symbol '{}' with handle: 0x{:x}",
+                    name, symbol.handle.handle
+                ));
+                Ok(symbol)
+            }
+            Err(error) => {
+                log_error(&format!(
+                    "Kernel symbol lookup failed: {}",
+                    error.full_message()
+                ));
+                Err(error)
+            }
+        }
+    }
+
+    /// [`Executable::get_kernel_symbol`] without logging, for probing names
+    /// that are expected to be missing.
+    fn lookup_symbol(&self, name: &str, agent: &Agent) -> Result<KernelSymbol> {
         let c_name = CString::new(name)
             .map_err(|_| HsaError::InvalidArgument(format!("Invalid kernel name: '{}'", name)))?;
 
         let mut symbol = bindings::hsa_executable_symbol_t { handle: 0 };
 
-        unsafe {
-            let status = bindings::hsa_executable_get_symbol_by_name(
+        let status = unsafe {
+            bindings::hsa_executable_get_symbol_by_name(
                 self.handle,
                 c_name.as_ptr(),
                 &agent.handle,
                 &mut symbol,
-            );
-
-            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
-                let error = HsaError::from_status_with_context(
-                    status,
-                    &format!("Failed to find kernel symbol '{}'", name),
-                );
-                log_error(&format!("Kernel symbol lookup failed: {}", error));
-
-                // Provide helpful suggestions
-                let detailed_error = match status {
-                    bindings::hsa_status_t_HSA_STATUS_ERROR_INVALID_SYMBOL_NAME => {
-                        format!("{}", error)
-                    }
-                    _ => error.to_string(),
-                };
+            )
+        };
 
-                return Err(HsaError::KernelNotFound(detailed_error));
-            }
+        if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+            let error = HsaError::from_status_with_context(
+                status,
+                &format!("Failed to find kernel symbol '{}'", name),
+            );
+            return Err(HsaError::KernelNotFound(error.full_message()));
         }
 
-        log_debug(&format!(
-            "Found kernel symbol '{}' with handle: 0x{:x}",
-            name, symbol.handle
-        ));
         Ok(KernelSymbol { handle: symbol })
     }
 
+    /// Look up the indirect function `name`, failing with `KernelNotFound`
+    /// if there is no such symbol or it is a kernel or variable instead.
+    pub fn get_indirect_function(&self, name: &str, agent: &Agent) -> Result<KernelSymbol> {
+        let symbol = self.get_kernel_symbol(name, agent)?;
+        let kind = symbol.kind()?;
+        if kind != SymbolKind::IndirectFunction {
+            return Err(HsaError::KernelNotFound(format!(
+                "Symbol '{}' is a {:?}, not an indirect function",
+                name, kind
+            )));
+        }
+        Ok(symbol)
+    }
+
     pub fn list_symbols(&self, agent: &Agent) -> Result<Vec<String>> {
         log_debug("Listing all symbols in executable");
 
@@ -231,7 +798,10 @@ impl Executable {
 
             if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
                 let error = HsaError::from_status_with_context(status, "Failed to iterate symbols");
-                log_error(&format!("Symbol iteration failed: {}", error));
+                log_error(&format!(
+                    "Symbol iteration failed: {}",
+                    error.full_message()
+                ));
                 return Err(error);
             }
         }
@@ -243,27 +813,92 @@ impl Executable {
 
         Ok(symbols)
     }
-}
 
-impl Drop for Executable {
-    fn drop(&mut self) {
-        log_debug("Dropping executable");
+    /// Call `f` on each symbol of the executable for `agent` without
+    /// collecting them first. Iteration stops early when `f` returns
+    /// `Ok(false)`, and stops with the error when it returns `Err`.
+    pub fn for_each_symbol(
+        &self,
+        agent: &Agent,
+        mut f: impl FnMut(&KernelSymbol) -> Result<bool>,
+    ) -> Result<()> {
+        let mut visitor = SymbolVisitor {
+            f: &mut f,
+            error: None,
+        };
 
-        if let Some(reader) = self.code_object_reader {
-            unsafe {
-                bindings::hsa_code_object_reader_destroy(reader);
-            }
+        let status = unsafe {
+            bindings::hsa_executable_iterate_agent_symbols(
+                self.handle,
+                agent.handle,
+                Some(visit_symbol_callback),
+                &mut visitor as *mut _ as *mut c_void,
+            )
+        };
+
+        if let Some(error) = visitor.error {
+            return Err(error);
+        }
+        if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS
+            && status != bindings::hsa_status_t_HSA_STATUS_INFO_BREAK
+        {
+            let error = HsaError::from_status_with_context(status, "Failed to iterate symbols");
+            log_error(&format!(
+                "Symbol iteration failed: {}",
+                error.full_message()
+            ));
+            return Err(error);
         }
+        Ok(())
+    }
+
+    /// Names of the program-scope symbols (variables and functions not tied
+    /// to any agent), which [`Executable::list_symbols`] does not report.
+    pub fn list_program_symbols(&self) -> Result<Vec<String>> {
+        log_debug("Listing program symbols in executable");
+
+        let mut symbols = Vec::new();
 
         unsafe {
-            let status = bindings::hsa_executable_destroy(self.handle);
+            let status = bindings::hsa_executable_iterate_program_symbols(
+                self.handle,
+                Some(collect_program_symbol_names_callback),
+                &mut symbols as *mut _ as *mut c_void,
+            );
+
             if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                let error =
+                    HsaError::from_status_with_context(status, "Failed to iterate program symbols");
                 log_error(&format!(
-                    "Failed to destroy executable: {}",
-                    HsaError::from_status(status)
+                    "Program symbol iteration failed: {}",
+                    error.full_message()
                 ));
+                return Err(error);
             }
         }
+
+        log_info(&format!(
+            "Found {} program symbols in executable",
+            symbols.len()
+        ));
+        for (i, symbol) in symbols.iter().enumerate() {
+            log_debug(&format!("  Program symbol {}: {}", i, symbol));
+        }
+
+        Ok(symbols)
+    }
+}
+
+impl Drop for Executable {
+    fn drop(&mut self) {
+        log_debug("Dropping executable");
+
+        if let Err(error) = self.destroy() {
+            log_error(&format!(
+                "Failed to destroy executable: {}",
+                error.full_message()
+            ));
+        }
     }
 }
 
@@ -272,6 +907,72 @@ pub struct KernelSymbol {
 }
 
 impl KernelSymbol {
+    pub fn name(&self) -> Result<String> {
+        let mut names = Vec::new();
+        let status = push_symbol_name(self.handle, &mut names);
+        if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+            return Err(HsaError::from_status_with_context(
+                status,
+                "Failed to get symbol name",
+            ));
+        }
+        names.pop().ok_or(HsaError::StringConversionError)
+    }
+
+    pub fn kind(&self) -> Result<SymbolKind> {
+        let mut kind: bindings::hsa_symbol_kind_t = 0;
+
+        unsafe {
+            let status = bindings::hsa_executable_symbol_get_info(
+                self.handle,
+                bindings::hsa_executable_symbol_info_t_HSA_EXECUTABLE_SYMBOL_INFO_TYPE,
+                &mut kind as *mut _ as *mut c_void,
+            );
+
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                return Err(HsaError::from_status_with_context(
+                    status,
+                    "Failed to get symbol kind",
+                ));
+            }
+        }
+
+        SymbolKind::from_raw(kind)
+            .ok_or_else(|| HsaError::InvalidArgument(format!("Unknown symbol kind: {}", kind)))
+    }
+
+    /// Handle of an indirect function, to be passed to kernels that take a
+    /// function pointer argument. Fails for kernels and variables.
+    pub fn indirect_function_object(&self) -> Result<u64> {
+        let kind = self.kind()?;
+        if kind != SymbolKind::IndirectFunction {
+            return Err(HsaError::InvalidArgument(format!(
+                "Symbol is a {:?}, not an indirect function",
+                kind
+            )));
+        }
+
+        let mut object = 0u64;
+
+        unsafe {
+            let status = bindings::hsa_executable_symbol_get_info(
+                self.handle,
+                bindings::hsa_executable_symbol_info_t_HSA_EXECUTABLE_SYMBOL_INFO_INDIRECT_FUNCTION_OBJECT,
+                &mut object as *mut _ as *mut c_void,
+            );
+
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                return Err(HsaError::from_status_with_context(
+                    status,
+                    "Failed to get indirect function object from symbol",
+                ));
+            }
+        }
+
+        log_debug(&format!("Indirect function object: 0x{:x}", object));
+        Ok(object)
+    }
+
     pub fn kernel_object(&self) -> Result<u64> {
         log_debug("Getting kernel object handle from symbol");
 
@@ -289,7 +990,10 @@ impl KernelSymbol {
                     status,
                     "Failed to get kernel object from symbol",
                 );
-                log_error(&format!("Kernel object retrieval failed: {}", error));
+                log_error(&format!(
+                    "Kernel object retrieval failed: {}",
+                    error.full_message()
+                ));
                 return Err(error);
             }
         }
@@ -298,6 +1002,71 @@ impl KernelSymbol {
         Ok(kernel_object)
     }
 
+    /// Dispatch the kernel over `problem` work-items with a workgroup size
+    /// chosen automatically, then return once the packet is submitted.
+    ///
+    /// The workgroup size is picked as follows:
+    ///
+    /// 1. The budget starts at 256 work-items, capped by the agent's maximum
+    ///    workgroup size and rounded down to a power of two.
+    /// 2. If the kernel needs more than 1 KiB of private (scratch) memory per
+    ///    work-item, the budget drops to a single wavefront, because scratch
+    ///    then limits occupancy more than small workgroups do.
+    /// 3. The budget is handed out by doubling the x, y and z extents in turn,
+    ///    skipping a dimension once it covers the problem or reaches the
+    ///    agent's per-dimension limit. Large 2D problems therefore get 16x16
+    ///    workgroups and 1D problems 256x1x1.
+    ///
+    /// The kernel's group segment (LDS) is allocated per workgroup and does
+    /// not influence the choice. The grid is rounded up as in
+    /// [`KernelDispatch::for_problem_size`], so the kernel must ignore
+    /// work-items outside `problem`. The queue must have been created by
+    /// [`Queue::create`] so that its agent is known.
+    pub fn launch_auto(
+        &self,
+        queue: &Queue,
+        problem: Dim3,
+        kernarg_address: *mut c_void,
+        completion_signal: &Signal,
+    ) -> Result<()> {
+        let agent = queue.agent().ok_or_else(|| {
+            HsaError::InvalidArgument(
+                "Cannot pick a workgroup size for a queue without a known agent".to_string(),
+            )
+        })?;
+
+        let private_segment_size = self.get_private_segment_size()?;
+        let group_segment_size = self.get_group_segment_size()?;
+
+        let mut budget = agent.workgroup_max_size()?.min(AUTO_WORKGROUP_BUDGET);
+        if private_segment_size > AUTO_PRIVATE_SEGMENT_LIMIT {
+            budget = budget.min(agent.wavefront_size()?);
+        }
+        if budget == 0 {
+            return Err(HsaError::InvalidArgument(
+                "Agent reports a maximum workgroup size of 0".to_string(),
+            ));
+        }
+        // Round down to a power of two.
+        let budget = 1 << budget.ilog2();
+
+        let workgroup = auto_workgroup_size(problem, budget, agent.workgroup_max_dim()?);
+        log_info(&format!(
+            "Auto-selected workgroup {}x{}x{} for problem {}x{}x{}",
+            workgroup.x, workgroup.y, workgroup.z, problem.x, problem.y, problem.z
+        ));
+
+        let dispatch = KernelDispatch {
+            kernel_object: self.kernel_object()?,
+            kernarg_address,
+            private_segment_size,
+            group_segment_size,
+            ..KernelDispatch::for_problem_size(problem, workgroup)?
+        };
+        dispatch.validate(queue, &agent)?;
+        dispatch.dispatch(queue, completion_signal)
+    }
+
     pub fn get_kernarg_segment_size(&self) -> Result<u32> {
         let mut size = 0u32;
 
@@ -316,6 +1085,26 @@ impl KernelSymbol {
         Ok(size)
     }
 
+    /// Alignment, in bytes, the kernel requires of its kernarg segment. This
+    /// is a power of two and at least 16.
+    pub fn kernarg_alignment(&self) -> Result<u32> {
+        let mut alignment = 0u32;
+
+        unsafe {
+            let status = bindings::hsa_executable_symbol_get_info(
+                self.handle,
+                bindings::hsa_executable_symbol_info_t_HSA_EXECUTABLE_SYMBOL_INFO_KERNEL_KERNARG_SEGMENT_ALIGNMENT,
+                &mut alignment as *mut _ as *mut c_void,
+            );
+
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                return Err(HsaError::from_status(status));
+            }
+        }
+
+        Ok(alignment)
+    }
+
     pub fn get_group_segment_size(&self) -> Result<u32> {
         let mut size = 0u32;
 
@@ -353,6 +1142,75 @@ impl KernelSymbol {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Dim3 {
+    pub x: u32,
+    pub y: u32,
+    pub z: u32,
+}
+
+impl Dim3 {
+    pub const fn new(x: u32, y: u32, z: u32) -> Self {
+        Self { x, y, z }
+    }
+
+    pub fn volume(&self) -> u64 {
+        self.x as u64 * self.y as u64 * self.z as u64
+    }
+}
+
+impl From<(u32, u32, u32)> for Dim3 {
+    fn from((x, y, z): (u32, u32, u32)) -> Self {
+        Self { x, y, z }
+    }
+}
+
+impl From<Dim3> for (u32, u32, u32) {
+    fn from(dim: Dim3) -> Self {
+        (dim.x, dim.y, dim.z)
+    }
+}
+
+/// Work-items per workgroup `launch_auto` aims for.
+pub(crate) const AUTO_WORKGROUP_BUDGET: u32 = 256;
+
+/// Per-work-item private segment size above which `launch_auto` limits
+/// workgroups to a single wavefront.
+const AUTO_PRIVATE_SEGMENT_LIMIT: u32 = 1024;
+
+/// Spread `budget` work-items over the dimensions of `problem` by doubling
+/// each dimension in turn while it is still smaller than the problem.
+pub(crate) fn auto_workgroup_size(problem: Dim3, budget: u32, max_dim: Dim3) -> Dim3 {
+    let extents = [problem.x, problem.y, problem.z];
+    let limits = [max_dim.x, max_dim.y, max_dim.z];
+    let mut workgroup = [1u32; 3];
+
+    loop {
+        let mut grew = false;
+        for axis in 0..3 {
+            let volume: u32 = workgroup.iter().product();
+            if volume * 2 <= budget
+                && workgroup[axis] < extents[axis]
+                && workgroup[axis] * 2 <= limits[axis]
+            {
+                workgroup[axis] *= 2;
+                grew = true;
+            }
+        }
+        if !grew {
+            break;
+        }
+    }
+
+    Dim3::new(workgroup[0], workgroup[1], workgroup[2])
+}
+
+/// Required alignment of the kernarg segment address.
+const KERNARG_ALIGNMENT: usize = 16;
+
+/// A kernel launch description. Dispatching only reads it, so one instance
+/// can be dispatched any number of times, e.g. once per frame, with the
+/// setters used to change what differs between launches.
 pub struct KernelDispatch {
     pub kernel_object: u64,
     pub kernarg_address: *mut c_void,
@@ -360,37 +1218,188 @@ pub struct KernelDispatch {
     pub grid_size: (u32, u32, u32),
     pub private_segment_size: u32,
     pub group_segment_size: u32,
+    /// Scope of the fence applied before the kernel starts; `None` means
+    /// [`FenceScope::System`].
+    pub acquire_fence: Option<FenceScope>,
+    /// Scope of the fence applied after the kernel completes; `None` means
+    /// [`FenceScope::System`].
+    pub release_fence: Option<FenceScope>,
 }
 
 impl KernelDispatch {
-    pub fn dispatch(&self, queue: &Queue, completion_signal: &Signal) -> Result<()> {
-        log_info(&format!(
-            "Dispatching kernel - Grid: {}x{}x{}, Workgroup: {}x{}x{}",
-            self.grid_size.0,
-            self.grid_size.1,
-            self.grid_size.2,
-            self.workgroup_size.0,
-            self.workgroup_size.1,
-            self.workgroup_size.2
+    /// Dispatch geometry for a logical `problem` size, with each grid
+    /// dimension rounded up to a multiple of the matching `workgroup`
+    /// dimension.
+    ///
+    /// The kernel object, kernarg address and segment sizes are left zeroed;
+    /// fill them in with struct update syntax:
+    ///
+    /// ```ignore
+    /// let dispatch = KernelDispatch {
+    ///     kernel_object,
+    ///     kernarg_address,
+    ///     ..KernelDispatch::for_problem_size(Dim3::new(1920, 1080, 1), Dim3::new(16, 16, 1))?
+    /// };
+    /// ```
+    pub fn for_problem_size(problem: Dim3, workgroup: Dim3) -> Result<Self> {
+        let round_up = |extent: u32, group: u32, axis: &str| -> Result<(u32, u16)> {
+            if extent == 0 {
+                return Err(HsaError::InvalidArgument(format!(
+                    "Problem size along {} must be greater than 0",
+                    axis
+                )));
+            }
+            let group_u16 = u16::try_from(group)
+                .ok()
+                .filter(|&g| g > 0)
+                .ok_or_else(|| {
+                    HsaError::InvalidArgument(format!(
+                        "Workgroup size {} along {} must be between 1 and {}",
+                        group,
+                        axis,
+                        u16::MAX
+                    ))
+                })?;
+            let grid = extent.div_ceil(group).checked_mul(group).ok_or_else(|| {
+                HsaError::InvalidArgument(format!(
+                    "Grid size along {} overflows when rounding {} up to a multiple of {}",
+                    axis, extent, group
+                ))
+            })?;
+            Ok((grid, group_u16))
+        };
+
+        let (grid_x, wg_x) = round_up(problem.x, workgroup.x, "x")?;
+        let (grid_y, wg_y) = round_up(problem.y, workgroup.y, "y")?;
+        let (grid_z, wg_z) = round_up(problem.z, workgroup.z, "z")?;
+
+        log_debug(&format!(
+            "Problem size {}x{}x{} rounded to grid {}x{}x{}",
+            problem.x, problem.y, problem.z, grid_x, grid_y, grid_z
         ));
 
-        let queue_ptr = queue.get();
+        Ok(Self {
+            kernel_object: 0,
+            kernarg_address: ptr::null_mut(),
+            workgroup_size: (wg_x, wg_y, wg_z),
+            grid_size: (grid_x, grid_y, grid_z),
+            private_segment_size: 0,
+            group_segment_size: 0,
+            acquire_fence: None,
+            release_fence: None,
+        })
+    }
 
-        // Get packet index
-        let packet_id = queue.add_write_index(1);
-        log_debug(&format!("Allocated packet ID: {}", packet_id));
+    /// Set the grid size for later dispatches. The grid is used as given; it
+    /// does not need to be a multiple of the workgroup size, in which case
+    /// the last workgroup along an axis is partial.
+    pub fn set_grid(&mut self, grid: Dim3) {
+        self.grid_size = grid.into();
+    }
 
-        // Get packet pointer
-        let packet_ptr = unsafe {
-            let base = queue_ptr.base_address as *mut bindings::hsa_kernel_dispatch_packet_t;
-            &mut *base.add((packet_id % queue_ptr.size as u64) as usize)
+    /// Point later dispatches at a different kernarg segment.
+    pub fn set_kernarg(&mut self, kernarg_address: *mut c_void) {
+        self.kernarg_address = kernarg_address;
+    }
+
+    /// Check the dispatch against `queue` and `agent` without submitting
+    /// anything, returning the first problem found.
+    ///
+    /// This covers the agent's device type, the kernel object, kernarg
+    /// alignment, workgroup and grid sizes against the agent's limits (see
+    /// [`Agent::validate_launch`]), and whether `queue` accepts kernel
+    /// dispatches. The completion signal is only supplied at dispatch time;
+    /// any `Signal` created by this crate already holds a valid handle.
+    pub fn validate(&self, queue: &Queue, agent: &Agent) -> Result<()> {
+        check_dispatch_device(agent)?;
+
+        if self.kernel_object == 0 {
+            return Err(HsaError::InvalidArgument(
+                "Kernel object handle is 0".to_string(),
+            ));
+        }
+
+        if self.kernarg_address as usize % KERNARG_ALIGNMENT != 0 {
+            return Err(HsaError::InvalidArgument(format!(
+                "Kernarg address {:p} is not {}-byte aligned",
+                self.kernarg_address, KERNARG_ALIGNMENT
+            )));
+        }
+
+        agent.validate_launch(self.grid(), self.workgroup())?;
+        queue.check_kernel_dispatch()?;
+
+        log_debug("Kernel dispatch configuration validated");
+        Ok(())
+    }
+
+    fn grid(&self) -> Dim3 {
+        Dim3::from(self.grid_size)
+    }
+
+    fn workgroup(&self) -> Dim3 {
+        Dim3::new(
+            self.workgroup_size.0 as u32,
+            self.workgroup_size.1 as u32,
+            self.workgroup_size.2 as u32,
+        )
+    }
+
+    /// Check the device type and launch dimensions against the queue's
+    /// agent. Queues without a known agent are not checked.
+    pub(crate) fn check_agent(&self, queue: &Queue) -> Result<()> {
+        if let Some(limits) = queue.launch_limits()? {
+            check_dispatch_device_type(&limits.agent, limits.device_type)?;
+            limits.check(self.grid(), self.workgroup())?;
+        }
+        Ok(())
+    }
+
+    /// Fail with `OutOfResources` if the queue's agent cannot back the
+    /// kernel's private segment.
+    ///
+    /// The runtime sizes scratch per wavefront and, when it cannot back every
+    /// wavefront the device could run, launches fewer at a time. The dispatch
+    /// can only fail outright when even a single wavefront does not fit the
+    /// agent's scratch limit, so that is what is checked here. Queues without
+    /// a known agent, and runtimes that do not report the limit, are not
+    /// checked.
+    pub(crate) fn check_scratch(&self, queue: &Queue) -> Result<()> {
+        if self.private_segment_size == 0 {
+            return Ok(());
+        }
+        let Some(limits) = queue.launch_limits()? else {
+            return Ok(());
+        };
+        let Some(available) = limits.scratch_per_work_item else {
+            log_debug("Scratch limit unavailable, skipping scratch check");
+            return Ok(());
         };
 
-        // Clear packet
-        unsafe {
-            std::ptr::write_bytes(packet_ptr, 0, 1);
+        if self.private_segment_size as u64 > available {
+            return Err(HsaError::OutOfResources(format!(
+                "insufficient scratch: kernel needs {} bytes per work-item, queue provides {}",
+                self.private_segment_size, available
+            )));
         }
+        Ok(())
+    }
 
+    /// Header of the dispatch packet, with unset fences at system scope.
+    pub(crate) fn header(&self) -> u16 {
+        make_dispatch_header(
+            self.acquire_fence.unwrap_or(FenceScope::System),
+            self.release_fence.unwrap_or(FenceScope::System),
+        )
+    }
+
+    /// Write everything but the header of a dispatch packet into the slot at
+    /// `packet`.
+    pub(crate) fn fill_packet(
+        &self,
+        packet: *mut bindings::hsa_kernel_dispatch_packet_t,
+        completion_signal: bindings::hsa_signal_t,
+    ) {
         // Determine dimensions
         let dimensions = if self.grid_size.2 > 1 {
             3
@@ -402,50 +1411,144 @@ impl KernelDispatch {
 
         log_debug(&format!("Using {} dimensions", dimensions));
 
-        // Setup dimensions
-        packet_ptr.setup = (dimensions as u16) << bindings::hsa_kernel_dispatch_packet_setup_t_HSA_KERNEL_DISPATCH_PACKET_SETUP_DIMENSIONS;
-
-        // Setup header with proper memory fencing
-        packet_ptr.header = (bindings::hsa_packet_type_t_HSA_PACKET_TYPE_KERNEL_DISPATCH as u16)
-            << bindings::hsa_packet_header_t_HSA_PACKET_HEADER_TYPE
-            | (bindings::hsa_fence_scope_t_HSA_FENCE_SCOPE_SYSTEM as u16)
-                << bindings::hsa_packet_header_t_HSA_PACKET_HEADER_SCACQUIRE_FENCE_SCOPE
-            | (bindings::hsa_fence_scope_t_HSA_FENCE_SCOPE_SYSTEM as u16)
-                << bindings::hsa_packet_header_t_HSA_PACKET_HEADER_SCRELEASE_FENCE_SCOPE;
-
-        // Set workgroup and grid sizes
-        packet_ptr.workgroup_size_x = self.workgroup_size.0;
-        packet_ptr.workgroup_size_y = self.workgroup_size.1;
-        packet_ptr.workgroup_size_z = self.workgroup_size.2;
-        packet_ptr.grid_size_x = self.grid_size.0;
-        packet_ptr.grid_size_y = self.grid_size.1;
-        packet_ptr.grid_size_z = self.grid_size.2;
-
-        // Set kernel object and arguments
-        packet_ptr.kernel_object = self.kernel_object;
-        packet_ptr.kernarg_address = self.kernarg_address;
-        packet_ptr.private_segment_size = self.private_segment_size;
-        packet_ptr.group_segment_size = self.group_segment_size;
-        packet_ptr.completion_signal = completion_signal.handle();
+        let mut body: bindings::hsa_kernel_dispatch_packet_t = unsafe { std::mem::zeroed() };
+
+        body.setup = (dimensions as u16) << bindings::hsa_kernel_dispatch_packet_setup_t_HSA_KERNEL_DISPATCH_PACKET_SETUP_DIMENSIONS;
+
+        body.workgroup_size_x = self.workgroup_size.0;
+        body.workgroup_size_y = self.workgroup_size.1;
+        body.workgroup_size_z = self.workgroup_size.2;
+        body.grid_size_x = self.grid_size.0;
+        body.grid_size_y = self.grid_size.1;
+        body.grid_size_z = self.grid_size.2;
+
+        body.kernel_object = self.kernel_object;
+        body.kernarg_address = self.kernarg_address;
+        body.private_segment_size = self.private_segment_size;
+        body.group_segment_size = self.group_segment_size;
+        body.completion_signal = completion_signal;
+
+        write_packet_body(packet, &body);
+    }
+
+    /// Submit the kernel to `queue`. Several threads may dispatch to the same
+    /// queue concurrently.
+    ///
+    /// When the queue's agent is known, the grid and workgroup are first
+    /// checked with [`Agent::validate_launch`].
+    pub fn dispatch(&self, queue: &Queue, completion_signal: &Signal) -> Result<()> {
+        log_info(&format!(
+            "Dispatching kernel - Grid: {}x{}x{}, Workgroup: {}x{}x{}",
+            self.grid_size.0,
+            self.grid_size.1,
+            self.grid_size.2,
+            self.workgroup_size.0,
+            self.workgroup_size.1,
+            self.workgroup_size.2
+        ));
+
+        self.check_agent(queue)?;
+        queue.check_kernel_dispatch()?;
+        self.check_scratch(queue)?;
+
+        // Claim a slot; this also waits for the ring to have room.
+        let packet_id = queue.claim_slots(1);
+        log_debug(&format!("Allocated packet ID: {}", packet_id));
+        clear_packet(queue.packet_slot(packet_id));
+
+        self.submit(queue, packet_id, completion_signal);
+        log_info("Kernel dispatch completed successfully");
+        Ok(())
+    }
+
+    /// Like [`KernelDispatch::dispatch`], but fails with
+    /// [`HsaError::QueueFull`] instead of waiting when the ring has no free
+    /// slot. Nothing is written to the queue in that case, so the launch can
+    /// simply be retried later.
+    pub fn try_dispatch(&self, queue: &Queue, completion_signal: &Signal) -> Result<()> {
+        self.check_agent(queue)?;
+        queue.check_kernel_dispatch()?;
+        self.check_scratch(queue)?;
+
+        let packet_id = queue.try_reserve().ok_or_else(|| {
+            HsaError::QueueFull(format!(
+                "no free packet slot in queue of {} packets",
+                queue.get_size()
+            ))
+        })?;
+        log_debug(&format!("Reserved packet ID: {}", packet_id));
+
+        self.submit(queue, packet_id, completion_signal);
+        Ok(())
+    }
+
+    /// Fill the already cleared slot `packet_id`, publish its header and
+    /// ring the doorbell.
+    fn submit(&self, queue: &Queue, packet_id: u64, completion_signal: &Signal) {
+        let packet = queue.packet_slot(packet_id);
+        self.fill_packet(packet, completion_signal.handle());
 
         log_debug(&format!(
             "Packet configured: kernel_object=0x{:x}, kernarg_address={:p}",
             self.kernel_object, self.kernarg_address
         ));
 
-        // Submit packet
-        queue.store_write_index(packet_id + 1);
-        log_debug(&format!("Updated queue write index to {}", packet_id + 1));
+        // The header goes last: once it is valid the packet processor may
+        // consume the packet.
+        publish_packet(packet, self.header());
+        queue.ring_doorbell(packet_id);
+    }
+}
 
-        // Ring doorbell
-        unsafe {
-            bindings::hsa_signal_store_relaxed(queue_ptr.doorbell_signal, packet_id as i64);
-        }
-        log_debug(&format!("Doorbell rung with packet ID: {}", packet_id));
+/// Only GPU agents consume the AMD kernel dispatch packets built here; AIE
+/// agents, for one, expect a different packet format.
+fn check_dispatch_device(agent: &Agent) -> Result<()> {
+    check_dispatch_device_type(agent, agent.device_type()?)
+}
 
-        log_info("Kernel dispatch completed successfully");
-        Ok(())
+fn check_dispatch_device_type(agent: &Agent, device_type: DeviceType) -> Result<()> {
+    if device_type != DeviceType::Gpu {
+        return Err(HsaError::Unsupported(format!(
+            "kernel dispatch packets cannot be submitted to {} agent 0x{:x}",
+            device_type, agent.handle.handle
+        )));
     }
+    Ok(())
+}
+
+/// Memory scope of a packet's acquire or release fence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FenceScope {
+    /// No fence.
+    None,
+    /// Coherent with the agent running the kernel only. Enough for kernels
+    /// whose inputs and outputs stay on one GPU, and cheaper than a system
+    /// fence because host-visible caches are not flushed.
+    Agent,
+    /// Coherent with the host and every other agent.
+    #[default]
+    System,
+}
+
+impl FenceScope {
+    pub(crate) fn raw(self) -> bindings::hsa_fence_scope_t {
+        match self {
+            FenceScope::None => bindings::hsa_fence_scope_t_HSA_FENCE_SCOPE_NONE,
+            FenceScope::Agent => bindings::hsa_fence_scope_t_HSA_FENCE_SCOPE_AGENT,
+            FenceScope::System => bindings::hsa_fence_scope_t_HSA_FENCE_SCOPE_SYSTEM,
+        }
+    }
+}
+
+/// Header for a kernel dispatch packet with the given acquire and release
+/// fence scopes.
+pub fn make_dispatch_header(acquire: FenceScope, release: FenceScope) -> u16 {
+    (bindings::hsa_packet_type_t_HSA_PACKET_TYPE_KERNEL_DISPATCH as u16)
+        << bindings::hsa_packet_header_t_HSA_PACKET_HEADER_TYPE
+        | (acquire.raw() as u16)
+            << bindings::hsa_packet_header_t_HSA_PACKET_HEADER_SCACQUIRE_FENCE_SCOPE
+        | (release.raw() as u16)
+            << bindings::hsa_packet_header_t_HSA_PACKET_HEADER_SCRELEASE_FENCE_SCOPE
 }
 
 // Callback function to collect symbol names
@@ -456,7 +1559,79 @@ unsafe extern "C" fn collect_symbol_names_callback(
     data: *mut c_void,
 ) -> bindings::hsa_status_t {
     let symbols = unsafe { &mut *(data as *mut Vec<String>) };
+    push_symbol_name(symbol, symbols)
+}
 
+unsafe extern "C" fn collect_program_symbol_names_callback(
+    _exec: bindings::hsa_executable_t,
+    symbol: bindings::hsa_executable_symbol_t,
+    data: *mut c_void,
+) -> bindings::hsa_status_t {
+    let symbols = unsafe { &mut *(data as *mut Vec<String>) };
+    push_symbol_name(symbol, symbols)
+}
+
+struct SymbolVisitor<'a> {
+    f: &'a mut dyn FnMut(&KernelSymbol) -> Result<bool>,
+    error: Option<HsaError>,
+}
+
+unsafe extern "C" fn visit_symbol_callback(
+    _exec: bindings::hsa_executable_t,
+    _agent: bindings::hsa_agent_t,
+    symbol: bindings::hsa_executable_symbol_t,
+    data: *mut c_void,
+) -> bindings::hsa_status_t {
+    let visitor = unsafe { &mut *(data as *mut SymbolVisitor) };
+    match (visitor.f)(&KernelSymbol { handle: symbol }) {
+        Ok(true) => bindings::hsa_status_t_HSA_STATUS_SUCCESS,
+        Ok(false) => bindings::hsa_status_t_HSA_STATUS_INFO_BREAK,
+        Err(error) => {
+            visitor.error = Some(error);
+            bindings::hsa_status_t_HSA_STATUS_INFO_BREAK
+        }
+    }
+}
+
+/// Function table of the AMD loader extension (version 1.01).
+fn amd_loader_table() -> Result<bindings::hsa_ven_amd_loader_1_01_pfn_t> {
+    // All entries are nullable function pointers, so zeroed is a valid table
+    let mut table: bindings::hsa_ven_amd_loader_1_01_pfn_t = unsafe { std::mem::zeroed() };
+
+    unsafe {
+        let status = bindings::hsa_system_get_major_extension_table(
+            bindings::hsa_extension_t_HSA_EXTENSION_AMD_LOADER as u16,
+            1,
+            std::mem::size_of::<bindings::hsa_ven_amd_loader_1_01_pfn_t>(),
+            &mut table as *mut _ as *mut c_void,
+        );
+
+        if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+            return Err(HsaError::from_status_with_context(
+                status,
+                "Failed to get AMD loader extension table",
+            ));
+        }
+    }
+
+    Ok(table)
+}
+
+unsafe extern "C" fn collect_loaded_code_objects_callback(
+    _executable: bindings::hsa_executable_t,
+    loaded_code_object: bindings::hsa_loaded_code_object_t,
+    data: *mut c_void,
+) -> bindings::hsa_status_t {
+    let loaded_code_objects =
+        unsafe { &mut *(data as *mut Vec<bindings::hsa_loaded_code_object_t>) };
+    loaded_code_objects.push(loaded_code_object);
+    bindings::hsa_status_t_HSA_STATUS_SUCCESS
+}
+
+fn push_symbol_name(
+    symbol: bindings::hsa_executable_symbol_t,
+    symbols: &mut Vec<String>,
+) -> bindings::hsa_status_t {
     // Get symbol name length
     let mut name_length = 0u32;
     let mut status = unsafe {
@@ -0,0 +1,229 @@
+use crate::error::log_debug;
+use crate::{Agent, Executable, Result};
+use std::collections::HashMap;
+use std::hash::{BuildHasher, RandomState};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Frozen executables shared between loads of the same code object for the
+/// same agent, so repeated requests for one kernel skip loading and
+/// freezing.
+///
+/// Entries are bucketed by a randomly keyed hash of the code object and the
+/// agent, and a hit compares the code object bytes, so distinct code objects
+/// never share an executable. The cache holds one reference to each
+/// executable; an evicted executable is destroyed once the last caller still
+/// using it drops its `Arc`.
+#[derive(Default)]
+pub struct ExecutableCache {
+    entries: Mutex<CodeObjectMap<Arc<Executable>>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct CacheKey {
+    hash: u64,
+    agent: Agent,
+}
+
+/// Values keyed by code object bytes and agent. The bytes are kept with each
+/// value and compared on lookup; the hash only picks the bucket.
+struct CodeObjectMap<T> {
+    hasher: RandomState,
+    buckets: HashMap<CacheKey, Vec<(Arc<[u8]>, T)>>,
+}
+
+impl<T> Default for CodeObjectMap<T> {
+    fn default() -> Self {
+        Self {
+            hasher: RandomState::new(),
+            buckets: HashMap::new(),
+        }
+    }
+}
+
+impl<T> CodeObjectMap<T> {
+    fn key(&self, agent: &Agent, code_object: &[u8]) -> CacheKey {
+        CacheKey {
+            hash: self.hasher.hash_one(code_object),
+            agent: *agent,
+        }
+    }
+
+    fn get(&self, key: &CacheKey, code_object: &[u8]) -> Option<&T> {
+        self.buckets
+            .get(key)?
+            .iter()
+            .find(|(bytes, _)| **bytes == *code_object)
+            .map(|(_, value)| value)
+    }
+
+    fn insert(&mut self, key: CacheKey, code_object: &[u8], value: T) {
+        self.buckets
+            .entry(key)
+            .or_default()
+            .push((Arc::from(code_object), value));
+    }
+
+    fn remove(&mut self, key: &CacheKey, code_object: &[u8]) -> Option<T> {
+        let bucket = self.buckets.get_mut(key)?;
+        let index = bucket
+            .iter()
+            .position(|(bytes, _)| **bytes == *code_object)?;
+        let (_, value) = bucket.swap_remove(index);
+        if bucket.is_empty() {
+            self.buckets.remove(key);
+        }
+        Some(value)
+    }
+
+    fn retain(&mut self, mut f: impl FnMut(&T) -> bool) {
+        self.buckets.retain(|_, bucket| {
+            bucket.retain(|(_, value)| f(value));
+            !bucket.is_empty()
+        });
+    }
+
+    fn clear(&mut self) {
+        self.buckets.clear();
+    }
+
+    fn len(&self) -> usize {
+        self.buckets.values().map(Vec::len).sum()
+    }
+}
+
+impl ExecutableCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The cached executable for `code_object` on `agent`, or a newly
+    /// loaded and frozen one that is cached for later calls.
+    ///
+    /// The cache stays locked while loading, so concurrent requests for a
+    /// missing code object load it only once.
+    pub fn get_or_load(&self, agent: &Agent, code_object: &[u8]) -> Result<Arc<Executable>> {
+        let mut entries = self.entries();
+        let key = entries.key(agent, code_object);
+
+        if let Some(executable) = entries.get(&key, code_object) {
+            log_debug(&format!(
+                "Executable cache hit for {} byte code object (hash 0x{:x})",
+                code_object.len(),
+                key.hash
+            ));
+            return Ok(Arc::clone(executable));
+        }
+
+        log_debug(&format!(
+            "Executable cache miss for {} byte code object (hash 0x{:x})",
+            code_object.len(),
+            key.hash
+        ));
+        let mut executable = Executable::create()?;
+        executable.load_code_object(agent, code_object)?;
+        executable.freeze()?;
+
+        let executable = Arc::new(executable);
+        entries.insert(key, code_object, Arc::clone(&executable));
+        Ok(executable)
+    }
+
+    /// Drop the cache's reference to the executable for `code_object` on
+    /// `agent`. Returns whether there was one.
+    pub fn evict(&self, agent: &Agent, code_object: &[u8]) -> bool {
+        let mut entries = self.entries();
+        let key = entries.key(agent, code_object);
+        entries.remove(&key, code_object).is_some()
+    }
+
+    /// Drop every executable no caller holds a reference to any more,
+    /// returning how many were evicted.
+    pub fn evict_unused(&self) -> usize {
+        let mut entries = self.entries();
+        let before = entries.len();
+        entries.retain(|executable| Arc::strong_count(executable) > 1);
+        let evicted = before - entries.len();
+        log_debug(&format!("Evicted {} unused cached executables", evicted));
+        evicted
+    }
+
+    pub fn clear(&self) {
+        self.entries().clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries().len() == 0
+    }
+
+    fn entries(&self) -> MutexGuard<'_, CodeObjectMap<Arc<Executable>>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings;
+
+    fn agent(handle: u64) -> Agent {
+        Agent {
+            handle: bindings::hsa_agent_t { handle },
+        }
+    }
+
+    #[test]
+    fn key_depends_on_bytes_and_agent() {
+        let map = CodeObjectMap::<u32>::default();
+        assert_eq!(map.key(&agent(1), b"abcd"), map.key(&agent(1), b"abcd"));
+        assert_ne!(map.key(&agent(1), b"abcd"), map.key(&agent(2), b"abcd"));
+        assert_ne!(map.key(&agent(1), b"abcd"), map.key(&agent(1), b"abce"));
+    }
+
+    #[test]
+    fn hit_and_miss() {
+        let mut map = CodeObjectMap::default();
+        let key = map.key(&agent(1), b"first");
+        map.insert(key, b"first", 1);
+
+        assert_eq!(map.get(&key, b"first"), Some(&1));
+        let other = map.key(&agent(1), b"other");
+        assert_eq!(map.get(&other, b"other"), None);
+        let elsewhere = map.key(&agent(2), b"first");
+        assert_eq!(map.get(&elsewhere, b"first"), None);
+    }
+
+    #[test]
+    fn colliding_code_objects_stay_apart() {
+        let mut map = CodeObjectMap::default();
+        // Same bucket, same length, different bytes
+        let key = map.key(&agent(1), b"aaaa");
+        map.insert(key, b"aaaa", 1);
+
+        assert_eq!(map.get(&key, b"bbbb"), None);
+        map.insert(key, b"bbbb", 2);
+        assert_eq!(map.get(&key, b"aaaa"), Some(&1));
+        assert_eq!(map.get(&key, b"bbbb"), Some(&2));
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn eviction() {
+        let mut map = CodeObjectMap::default();
+        let first = map.key(&agent(1), b"first");
+        let second = map.key(&agent(1), b"second");
+        map.insert(first, b"first", 1);
+        map.insert(second, b"second", 2);
+
+        assert_eq!(map.remove(&first, b"first"), Some(1));
+        assert_eq!(map.remove(&first, b"first"), None);
+        assert_eq!(map.get(&second, b"second"), Some(&2));
+
+        map.retain(|&value| value != 2);
+        assert_eq!(map.len(), 0);
+        assert!(map.buckets.is_empty());
+    }
+}
@@ -5,20 +5,39 @@
 
 mod agent;
 mod bindings;
+mod code_object;
 mod context;
+mod dispatcher;
 pub mod error;
 mod executable;
-mod memory;
+mod executable_cache;
+pub mod memory;
+mod memory_pool;
 mod queue;
 mod signal;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod virtual_memory;
 
-pub use agent::{Agent, DeviceType};
+pub use agent::{Agent, AgentInfo, CacheInfo, DeviceType, ImageSupport, MachineModel};
 pub use context::HsaContext;
+pub use dispatcher::{MultiQueueDispatcher, PriorityDispatcher};
 pub use error::{HsaError, Result};
-pub use executable::{Executable, KernelDispatch, KernelSymbol};
-pub use memory::{Memory, MemoryRegion};
-pub use queue::Queue;
-pub use signal::Signal;
+pub use executable::{
+    CodeObjectReader, Dim3, Executable, FenceScope, FloatRoundingMode, KernelDispatch,
+    KernelSymbol, Profile, SymbolKind, make_dispatch_header,
+};
+pub use executable_cache::ExecutableCache;
+pub use memory::{
+    AccessGuard, AsyncCopy, CopyEngine, CopyRect, DmaBufFd, Memory, MemoryRegion, MemoryUsage,
+    MemoryWriter,
+};
+pub use memory_pool::{MemoryPool, PoolAccess};
+pub use queue::{PacketReservation, Queue, QueueError, QueueFeatures, QueueInfo, QueueType};
+#[cfg(feature = "signal-trace")]
+pub use signal::SignalEvent;
+pub use signal::{JoinSignal, Signal, SignalCondition, SignalFd, SignalOp, SignalPool, SignalRef};
+pub use virtual_memory::{PhysicalMemory, VirtualMemory};
 
 /// Initialize the HSA runtime
 pub fn init() -> Result<()> {
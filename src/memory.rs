@@ -1,16 +1,134 @@
 use crate::bindings;
 use crate::error::{log_debug, log_error};
-use crate::{Agent, HsaError, Result};
+use crate::{Agent, DeviceType, HsaError, MemoryPool, PoolAccess, Result, Signal};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Read};
 use std::marker::PhantomData;
-use std::os::raw::c_void;
+use std::os::fd::{AsRawFd, RawFd};
+use std::os::raw::{c_int, c_void};
 use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "memory-debug")]
+use std::{
+    collections::BTreeMap,
+    sync::{LazyLock, MutexGuard},
+};
+
+/// Size of the host staging buffer [`Memory::copy_from_reader`] uses for
+/// memory the host cannot write directly.
+const STAGING_CHUNK_SIZE: usize = 4 << 20;
+
+static TOTAL_ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+/// Bytes currently allocated through [`MemoryRegion::allocate`] and not yet
+/// freed, across all regions. Useful for asserting that a test released
+/// everything it allocated.
+pub fn total_allocated() -> usize {
+    TOTAL_ALLOCATED.load(Ordering::Relaxed)
+}
+
+/// A live allocation recorded by the `memory-debug` feature.
+#[cfg(feature = "memory-debug")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AllocationInfo {
+    pub base: u64,
+    /// Size in bytes, as requested.
+    pub size: usize,
+    pub region: MemoryRegion,
+}
+
+#[cfg(feature = "memory-debug")]
+impl AllocationInfo {
+    /// Whether `addr` lies inside the allocation.
+    pub fn contains(&self, addr: u64) -> bool {
+        addr >= self.base && addr - self.base < self.size as u64
+    }
+
+    /// Bytes from the end of the allocation to `addr`, or `None` if `addr`
+    /// is inside it or before it.
+    pub fn bytes_past_end(&self, addr: u64) -> Option<u64> {
+        addr.checked_sub(self.base + self.size as u64)
+    }
+}
+
+/// Live allocations keyed by base address (`memory-debug` feature).
+#[cfg(feature = "memory-debug")]
+static LIVE_ALLOCATIONS: LazyLock<Mutex<BTreeMap<u64, AllocationInfo>>> =
+    LazyLock::new(|| Mutex::new(BTreeMap::new()));
+
+#[cfg(feature = "memory-debug")]
+fn live_allocations() -> MutexGuard<'static, BTreeMap<u64, AllocationInfo>> {
+    LIVE_ALLOCATIONS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// The live allocation with the highest base address at or below `addr`,
+/// for turning a fault address into a diagnostic. The address may still lie
+/// past the end of the returned allocation; check
+/// [`AllocationInfo::contains`] or [`AllocationInfo::bytes_past_end`].
+///
+/// Only allocations made through [`MemoryRegion::allocate`] while the
+/// `memory-debug` feature is enabled are tracked.
+#[cfg(feature = "memory-debug")]
+pub fn which_allocation(addr: u64) -> Option<AllocationInfo> {
+    live_allocations()
+        .range(..=addr)
+        .next_back()
+        .map(|(_, info)| *info)
+}
 
 #[derive(Debug, Clone, Copy)]
 pub struct MemoryRegion {
     pub(crate) handle: bindings::hsa_region_t,
+    pub(crate) agent: Agent,
+}
+
+impl PartialEq for MemoryRegion {
+    fn eq(&self, other: &Self) -> bool {
+        self.handle.handle == other.handle.handle
+    }
+}
+
+impl Eq for MemoryRegion {}
+
+impl Hash for MemoryRegion {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.handle.handle.hash(state);
+    }
+}
+
+/// Expected use of an allocation, for [`MemoryRegion::allocate_with_hint`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryUsage {
+    /// Used almost only by the agent: coarse-grained memory the host cannot
+    /// map, typically VRAM.
+    DeviceLocal,
+    /// Read or written by the host while the agent uses it: fine-grained,
+    /// host-accessible memory.
+    HostVisible,
+    /// Written once and then read many times: coarse-grained memory, whose
+    /// contents the agent may cache without coherence traffic.
+    ReadMostly,
 }
 
 impl MemoryRegion {
+    /// The agent this region was discovered through.
+    pub fn agent(&self) -> Agent {
+        self.agent
+    }
+
+    /// The memory pool backing this region. ROCr implements regions and
+    /// pools with the same underlying objects, so the handles coincide.
+    pub(crate) fn pool(&self) -> MemoryPool {
+        MemoryPool {
+            handle: bindings::hsa_amd_memory_pool_t {
+                handle: self.handle.handle,
+            },
+            agent: self.agent,
+        }
+    }
+
     pub fn segment(&self) -> Result<bindings::hsa_region_segment_t> {
         let mut segment = 0u32;
         unsafe {
@@ -106,12 +224,88 @@ impl MemoryRegion {
         Ok(allowed)
     }
 
+    /// Whether the host can access allocations from this region directly.
+    pub fn is_host_accessible(&self) -> Result<bool> {
+        let mut accessible = false;
+        unsafe {
+            let status = bindings::hsa_region_get_info(
+                self.handle,
+                bindings::hsa_amd_region_info_s_HSA_AMD_REGION_INFO_HOST_ACCESSIBLE,
+                &mut accessible as *mut _ as *mut c_void,
+            );
+
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                return Err(HsaError::from_status_with_context(
+                    status,
+                    "Failed to get memory region host accessibility",
+                ));
+            }
+        }
+        Ok(accessible)
+    }
+
+    /// Allocate `size` bytes from whichever global region of this region's
+    /// agent best suits `usage`.
+    ///
+    /// This region is used when it suits `usage` as well as any other, and
+    /// also when none of the agent's regions suits it at all. Plain
+    /// [`MemoryRegion::allocate`] always uses this region.
+    pub fn allocate_with_hint(&self, size: usize, usage: MemoryUsage) -> Result<Memory> {
+        let region = self.region_for(usage)?;
+        log_debug(&format!(
+            "Memory usage {:?} placed in region 0x{:x}",
+            usage, region.handle.handle
+        ));
+        region.allocate(size)
+    }
+
+    fn region_for(&self, usage: MemoryUsage) -> Result<MemoryRegion> {
+        // (region, fine grained, host accessible), with this region first so
+        // it wins ties.
+        let mut candidates = Vec::new();
+        for region in std::iter::once(*self).chain(self.agent.iterate_memory_regions()?) {
+            if region.segment()? != bindings::hsa_region_segment_t_HSA_REGION_SEGMENT_GLOBAL
+                || !region.runtime_alloc_allowed()?
+            {
+                continue;
+            }
+            let fine = region.global_flags()?
+                & bindings::hsa_region_global_flag_t_HSA_REGION_GLOBAL_FLAG_FINE_GRAINED
+                != 0;
+            candidates.push((region, fine, region.is_host_accessible()?));
+        }
+
+        let preferences: &[fn(bool, bool) -> bool] = match usage {
+            MemoryUsage::DeviceLocal => &[|fine, host| !fine && !host, |fine, _| !fine],
+            MemoryUsage::HostVisible => &[|fine, host| fine && host, |_, host| host],
+            MemoryUsage::ReadMostly => &[|fine, _| !fine],
+        };
+        for prefers in preferences {
+            if let Some(&(region, _, _)) = candidates
+                .iter()
+                .find(|&&(_, fine, host)| prefers(fine, host))
+            {
+                return Ok(region);
+            }
+        }
+
+        Ok(*self)
+    }
+
     pub fn allocate(&self, size: usize) -> Result<Memory> {
         log_debug(&format!(
             "Allocating {} bytes from memory region 0x{:x}",
             size, self.handle.handle
         ));
 
+        // The runtime's handling of zero-sized allocations is
+        // implementation-defined and can hand out a pointer that faults.
+        if size == 0 {
+            return Err(HsaError::InvalidArgument(
+                "cannot allocate zero bytes".to_string(),
+            ));
+        }
+
         // Check if allocation is allowed
         if !self.runtime_alloc_allowed()? {
             return Err(HsaError::MemoryAllocationFailed(
@@ -137,11 +331,24 @@ impl MemoryRegion {
                     status,
                     &format!("Failed to allocate {} bytes from memory region", size),
                 );
-                log_error(&format!("Memory allocation failed: {}", error));
-                return Err(HsaError::MemoryAllocationFailed(error.to_string()));
+                log_error(&format!(
+                    "Memory allocation failed: {}",
+                    error.full_message()
+                ));
+                return Err(HsaError::MemoryAllocationFailed(error.full_message()));
             }
         }
 
+        TOTAL_ALLOCATED.fetch_add(size, Ordering::Relaxed);
+        #[cfg(feature = "memory-debug")]
+        live_allocations().insert(
+            ptr as u64,
+            AllocationInfo {
+                base: ptr as u64,
+                size,
+                region: *self,
+            },
+        );
         log_debug(&format!(
             "Successfully allocated {} bytes at address {:p}",
             size, ptr
@@ -150,6 +357,7 @@ impl MemoryRegion {
         Ok(Memory {
             ptr,
             size,
+            region: *self,
             _phantom: PhantomData,
         })
     }
@@ -158,6 +366,7 @@ impl MemoryRegion {
 pub struct Memory {
     ptr: *mut c_void,
     size: usize,
+    region: MemoryRegion,
     _phantom: PhantomData<[u8]>,
 }
 
@@ -166,6 +375,8 @@ impl Memory {
         self.ptr
     }
 
+    /// The allocation as bytes. The length is always [`Memory::size`], the
+    /// size that was requested, even if the runtime reserved more.
     pub fn as_slice(&self) -> &[u8] {
         unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.size) }
     }
@@ -174,6 +385,121 @@ impl Memory {
         unsafe { std::slice::from_raw_parts_mut(self.ptr as *mut u8, self.size) }
     }
 
+    /// Append-style typed writer starting at offset 0, e.g. for packing
+    /// kernel arguments.
+    pub fn writer(&mut self) -> MemoryWriter<'_> {
+        MemoryWriter {
+            memory: self,
+            offset: 0,
+        }
+    }
+
+    /// Copy a rectangle of `rect.height` rows of `rect.width` bytes from
+    /// `src` into this allocation, stepping `rect.src_pitch` bytes between
+    /// rows of `src` and `rect.dst_pitch` bytes between rows here.
+    ///
+    /// Padding bytes between rows are left untouched. Fails without copying
+    /// anything if the rectangle does not fit either buffer, or for memory
+    /// the host cannot write directly.
+    pub fn copy_rect(&mut self, src: &[u8], rect: CopyRect) -> Result<()> {
+        rect.check_fits(src.len(), rect.src_pitch, "source")?;
+        rect.check_fits(self.size, rect.dst_pitch, "destination")?;
+        if !self.is_host_accessible()? {
+            return Err(HsaError::InvalidAllocation(format!(
+                "Memory at {:p} is not host accessible",
+                self.ptr
+            )));
+        }
+
+        let dst = self.as_mut_slice();
+        for row in 0..rect.height {
+            let src_start = row * rect.src_pitch;
+            let dst_start = row * rect.dst_pitch;
+            dst[dst_start..dst_start + rect.width]
+                .copy_from_slice(&src[src_start..src_start + rect.width]);
+        }
+
+        log_debug(&format!(
+            "Copied {}x{} byte rect into {:p} (src pitch {}, dst pitch {})",
+            rect.width, rect.height, self.ptr, rect.src_pitch, rect.dst_pitch
+        ));
+        Ok(())
+    }
+
+    /// Fill this allocation from `reader`, returning the number of bytes
+    /// written. Reading stops at end of input or once the allocation is full,
+    /// whichever comes first.
+    ///
+    /// Host-accessible memory is read into directly. Anything else goes
+    /// through a page-locked staging buffer of at most 4 MiB that is copied
+    /// to the device after each fill, so the input never has to be held in
+    /// host memory as a whole.
+    pub fn copy_from_reader(&mut self, reader: &mut impl Read) -> Result<usize> {
+        if self.is_host_accessible()? {
+            let written = read_full(reader, self.as_mut_slice())?;
+            log_debug(&format!(
+                "Read {} bytes into {:p} from reader",
+                written, self.ptr
+            ));
+            return Ok(written);
+        }
+
+        if self.size == 0 {
+            return Ok(0);
+        }
+        let mut staging = vec![0u8; STAGING_CHUNK_SIZE.min(self.size)];
+        let host_ptr = staging.as_mut_ptr() as *mut c_void;
+        let locked_ptr = lock_host_buffer(host_ptr, staging.len(), &self.region.agent)?;
+
+        let result = self.copy_from_reader_staged(reader, &mut staging, locked_ptr);
+        unsafe {
+            bindings::hsa_amd_memory_unlock(host_ptr);
+        }
+
+        let written = result?;
+        log_debug(&format!(
+            "Streamed {} bytes into {:p} through a {} byte staging buffer",
+            written,
+            self.ptr,
+            staging.len()
+        ));
+        Ok(written)
+    }
+
+    fn copy_from_reader_staged(
+        &mut self,
+        reader: &mut impl Read,
+        staging: &mut [u8],
+        locked_ptr: *mut c_void,
+    ) -> Result<usize> {
+        let mut written = 0;
+        while written < self.size {
+            let chunk = staging.len().min(self.size - written);
+            let read = read_full(reader, &mut staging[..chunk])?;
+            if read == 0 {
+                break;
+            }
+
+            unsafe {
+                let dst = (self.ptr as *mut u8).add(written) as *mut c_void;
+                let status = bindings::hsa_memory_copy(dst, locked_ptr, read);
+
+                if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                    return Err(HsaError::from_status_with_context(
+                        status,
+                        &format!("Failed to copy {} bytes to {:p}", read, dst),
+                    ));
+                }
+            }
+
+            written += read;
+            if read < chunk {
+                break;
+            }
+        }
+        Ok(written)
+    }
+
     pub fn allow_access(&self, agents: &[Agent]) -> Result<()> {
         if agents.is_empty() {
             log_debug("No agents specified for memory access - allowing default access");
@@ -199,7 +525,10 @@ impl Memory {
                     status,
                     "Failed to allow memory access for agents",
                 );
-                log_error(&format!("Memory access permission failed: {}", error));
+                log_error(&format!(
+                    "Memory access permission failed: {}",
+                    error.full_message()
+                ));
                 return Err(error);
             }
         }
@@ -208,13 +537,449 @@ impl Memory {
         Ok(())
     }
 
+    /// Set the agents allowed to access this allocation to `agents`, unlike
+    /// [`Memory::allow_access`] treating an empty list as "owner only"
+    /// instead of leaving the default access in place.
+    ///
+    /// Every agent in the system is checked against the backing pool first,
+    /// and nothing is changed if a listed agent can never access it. HSA
+    /// cannot fully deny access, though:
+    ///
+    /// - The agent owning the allocation's region always keeps access,
+    ///   whether or not it is listed.
+    /// - Agents the pool grants access by default (e.g. every agent for
+    ///   system memory) cannot be excluded. If such an agent is not listed
+    ///   this fails with [`HsaError::Unsupported`].
+    /// - `hsa_amd_agents_allow_access` has no revoke counterpart, so agents
+    ///   granted access earlier may keep their mapping; see
+    ///   [`Memory::scoped_access`]. Call this right after allocating.
+    pub fn restrict_access(&self, agents: &[Agent]) -> Result<()> {
+        let pool = self.region.pool();
+        for agent in agents {
+            if *agent != self.region.agent && pool.accessible_by(agent)? == PoolAccess::Never {
+                return Err(HsaError::InvalidAgent(format!(
+                    "Agent 0x{:x} cannot access memory owned by agent 0x{:x}",
+                    agent.handle.handle, self.region.agent.handle.handle
+                )));
+            }
+        }
+        for agent in Agent::find_all()? {
+            if agent != self.region.agent
+                && !agents.contains(&agent)
+                && pool.accessible_by(&agent)? == PoolAccess::Allowed
+            {
+                return Err(HsaError::Unsupported(format!(
+                    "Agent 0x{:x} can access memory at {:p} by default and cannot be denied",
+                    agent.handle.handle, self.ptr
+                )));
+            }
+        }
+
+        log_debug(&format!(
+            "Restricting access to {:p} to {} agents",
+            self.ptr,
+            agents.len()
+        ));
+        if agents.is_empty() {
+            self.allow_access(&[self.region.agent])
+        } else {
+            self.allow_access(agents)
+        }
+    }
+
+    /// Grant peer GPUs direct access to this allocation.
+    ///
+    /// Each peer is first checked against the pool backing the allocation;
+    /// if any peer can never access it (for example because the GPUs are not
+    /// connected by a peer-capable link), nothing is granted and an error
+    /// naming that peer is returned. Peers that pass the check are granted
+    /// access with `hsa_amd_agents_allow_access`, which maps the allocation
+    /// into their address space.
+    pub fn enable_peer_access(&self, peers: &[Agent]) -> Result<()> {
+        let pool = self.region.pool();
+
+        for peer in peers {
+            if pool.accessible_by(peer)? == PoolAccess::Never {
+                return Err(HsaError::InvalidAgent(format!(
+                    "Agent 0x{:x} cannot access memory owned by agent 0x{:x}",
+                    peer.handle.handle, self.region.agent.handle.handle
+                )));
+            }
+        }
+
+        let mut agents = Vec::with_capacity(peers.len() + 1);
+        agents.push(self.region.agent);
+        agents.extend_from_slice(peers);
+        self.allow_access(&agents)
+    }
+
+    /// Grant `agents` access to this allocation until the returned guard is
+    /// dropped.
+    ///
+    /// HSA has no call that withdraws access: `hsa_amd_agents_allow_access`
+    /// only states which agents may access an allocation. On drop the guard
+    /// re-applies that call with just the agent owning the allocation's
+    /// region, which is the access set a fresh allocation starts with.
+    /// Whether agents granted in between lose their mapping at that point is
+    /// up to the runtime and kernel driver, so treat the guard as best-effort
+    /// scoping rather than an isolation boundary between tenants.
+    pub fn scoped_access(&self, agents: &[Agent]) -> Result<AccessGuard<'_>> {
+        self.allow_access(agents)?;
+        Ok(AccessGuard { memory: self })
+    }
+
+    pub fn region(&self) -> MemoryRegion {
+        self.region
+    }
+
+    /// Exactly the number of bytes requested from [`MemoryRegion::allocate`].
+    /// See [`Memory::allocated_size`] for what the runtime actually reserved.
     pub fn size(&self) -> usize {
         self.size
     }
 
+    /// CRC-32 (IEEE) of the allocation's bytes, for checking that data
+    /// survived a round trip through the device unchanged.
+    ///
+    /// Fails for memory the host cannot read directly.
+    pub fn crc32(&self) -> Result<u32> {
+        if !self.is_host_accessible()? {
+            return Err(HsaError::InvalidAllocation(format!(
+                "Memory at {:p} is not host accessible",
+                self.ptr
+            )));
+        }
+        Ok(crc32(self.as_slice()))
+    }
+
+    /// Owned copies of the allocation read as consecutive `T` records.
+    ///
+    /// Trailing bytes that do not make up a whole record are ignored. Fails
+    /// for memory the host cannot read directly.
+    pub fn records<T: Copy>(&self) -> Result<impl Iterator<Item = T> + '_> {
+        let record_size = std::mem::size_of::<T>();
+        if record_size == 0 {
+            return Err(HsaError::InvalidArgument(
+                "Record type must not be zero-sized".to_string(),
+            ));
+        }
+        if !self.is_host_accessible()? {
+            return Err(HsaError::InvalidAllocation(format!(
+                "Memory at {:p} is not host accessible",
+                self.ptr
+            )));
+        }
+
+        let count = self.size / record_size;
+        let remainder = self.size % record_size;
+        if remainder != 0 {
+            log_debug(&format!(
+                "Memory at {:p}: ignoring {} trailing bytes after {} records of {} bytes",
+                self.ptr, remainder, count, record_size
+            ));
+        }
+
+        let base = self.ptr as *const T;
+        Ok((0..count).map(move |i| unsafe { ptr::read_unaligned(base.add(i)) }))
+    }
+
+    /// Number of bytes the runtime reserved for this allocation, which is
+    /// [`Memory::size`] rounded up to the allocation granule. Bytes beyond
+    /// `size` belong to the allocation but are not covered by the slices.
+    pub fn allocated_size(&self) -> Result<usize> {
+        let allocated = self.pointer_info()?.sizeInBytes;
+        log_debug(&format!(
+            "Memory at {:p}: requested {} bytes, allocated {} bytes",
+            self.ptr, self.size, allocated
+        ));
+        Ok(allocated)
+    }
+
     pub fn is_null(&self) -> bool {
         self.ptr.is_null()
     }
+
+    /// Whether the host can read and write this allocation directly. If not,
+    /// reading it through [`Memory::as_slice`] returns garbage.
+    pub fn is_host_accessible(&self) -> Result<bool> {
+        self.region.is_host_accessible()
+    }
+
+    /// The GPU virtual address of this allocation, as reported by
+    /// `hsa_amd_pointer_info` (`agentBaseAddress`). With HSA's unified
+    /// address space this normally equals [`Memory::as_ptr`].
+    ///
+    /// This is not a physical or bus address; HSA does not expose those. For
+    /// handing memory to other devices such as NICs, use
+    /// [`Memory::export_dmabuf`].
+    pub fn agent_address(&self) -> Result<u64> {
+        let info = self.pointer_info()?;
+        let address = info.agentBaseAddress as u64;
+
+        log_debug(&format!("Agent address of {:p}: 0x{:x}", self.ptr, address));
+        Ok(address)
+    }
+
+    /// Export the allocation as a Linux dma-buf, for sharing with other
+    /// devices and subsystems (NICs, video encoders, ...).
+    pub fn export_dmabuf(&self) -> Result<DmaBufFd> {
+        let mut fd: c_int = -1;
+        let mut offset = 0u64;
+
+        unsafe {
+            let status =
+                bindings::hsa_amd_portable_export_dmabuf(self.ptr, self.size, &mut fd, &mut offset);
+
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                let error = HsaError::from_status_with_context(
+                    status,
+                    &format!("Failed to export {:p} as dma-buf", self.ptr),
+                );
+                log_error(&format!("dma-buf export failed: {}", error.full_message()));
+                return Err(error);
+            }
+        }
+
+        log_debug(&format!(
+            "Exported {:p} ({} bytes) as dma-buf fd {} at offset {}",
+            self.ptr, self.size, fd, offset
+        ));
+        Ok(DmaBufFd { fd, offset })
+    }
+
+    /// The runtime's bookkeeping for this allocation.
+    pub(crate) fn pointer_info(&self) -> Result<bindings::hsa_amd_pointer_info_t> {
+        let mut info: bindings::hsa_amd_pointer_info_t = unsafe { std::mem::zeroed() };
+        info.size = std::mem::size_of::<bindings::hsa_amd_pointer_info_t>() as u32;
+
+        unsafe {
+            let status = bindings::hsa_amd_pointer_info(
+                self.ptr,
+                &mut info,
+                None,
+                ptr::null_mut(),
+                ptr::null_mut(),
+            );
+
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                return Err(HsaError::from_status_with_context(
+                    status,
+                    &format!("Failed to get pointer info for {:p}", self.ptr),
+                ));
+            }
+        }
+
+        if info.type_ != bindings::hsa_amd_pointer_type_t_HSA_EXT_POINTER_TYPE_HSA {
+            return Err(HsaError::InvalidAllocation(format!(
+                "{:p} is not an HSA allocation (pointer type {})",
+                self.ptr, info.type_
+            )));
+        }
+
+        Ok(info)
+    }
+
+    /// Start copying the first `dst.len()` bytes of this allocation into
+    /// `dst` using the runtime's DMA engines.
+    ///
+    /// `dst` is page-locked and mapped for `agent` (normally the GPU owning
+    /// this allocation) for the duration of the copy. The returned guard owns
+    /// `dst` and exposes the completion signal, which drops to 0 once the
+    /// data has landed; [`AsyncCopy::wait`] hands the buffer back. Dropping
+    /// the guard waits for completion, unlocks the buffer and frees it, and
+    /// leaking it leaks the buffer too, so the engine never writes into
+    /// memory the caller can reach again early.
+    pub fn async_copy_to_host(&self, dst: Vec<u8>, agent: &Agent) -> Result<AsyncCopy<'_>> {
+        self.async_copy_to_host_on(dst, agent, CopyEngine::Auto)
+    }
+
+    /// [`Memory::async_copy_to_host`] with an explicit copy engine
+    /// preference; see [`CopyEngine`] for what each choice guarantees.
+    pub fn async_copy_to_host_on(
+        &self,
+        mut dst: Vec<u8>,
+        agent: &Agent,
+        engine: CopyEngine,
+    ) -> Result<AsyncCopy<'_>> {
+        if dst.len() > self.size {
+            return Err(HsaError::InvalidArgument(format!(
+                "Destination of {} bytes is larger than the {} byte allocation",
+                dst.len(),
+                self.size
+            )));
+        }
+
+        let host_agent = Agent::find(DeviceType::Cpu)?;
+        let signal = Signal::create(1)?;
+        let host_ptr = dst.as_mut_ptr() as *mut c_void;
+        let locked_ptr = lock_host_buffer(host_ptr, dst.len(), agent)?;
+        let size = dst.len();
+
+        let copy = AsyncCopy {
+            signal,
+            host_ptr,
+            buffer: dst,
+            _source: PhantomData,
+        };
+
+        let request = CopyRequest {
+            dst: locked_ptr,
+            dst_agent: &host_agent,
+            src: self.ptr,
+            src_agent: &self.region.agent,
+            size,
+            dependencies: &[],
+            completion_signal: &copy.signal,
+            engine,
+        };
+        if let Err(error) = request.issue() {
+            // Nothing was queued, so let the guard unlock without waiting.
+            copy.signal.store(0);
+            return Err(error);
+        }
+
+        log_debug(&format!(
+            "Started async copy of {} bytes from {:p} to host buffer {:p}",
+            size, self.ptr, host_ptr
+        ));
+        Ok(copy)
+    }
+
+    /// Upload `src` to the start of this allocation once every signal in
+    /// `wait_for` has reached 0, without blocking the host.
+    ///
+    /// The dependencies are handed to the copy engine, so a buffer still
+    /// being read by an earlier kernel can be refilled as soon as that kernel
+    /// signals completion. `src` is staged in a page-locked copy, so it may
+    /// be reused as soon as this returns. `completion` is decremented by 1
+    /// once the data has landed and the staging copy has been released. The
+    /// handler keeps `completion` and the dependencies alive until then.
+    ///
+    /// # Safety
+    ///
+    /// The copy writes into this allocation after the call returns, so
+    /// `self` must not be freed until `completion` has been decremented.
+    pub unsafe fn copy_from_slice_after(
+        &mut self,
+        src: &[u8],
+        wait_for: &[Arc<Signal>],
+        completion: Arc<Signal>,
+        agent: &Agent,
+    ) -> Result<()> {
+        if src.len() > self.size {
+            return Err(HsaError::InvalidArgument(format!(
+                "Source of {} bytes is larger than the {} byte allocation",
+                src.len(),
+                self.size
+            )));
+        }
+
+        let host_agent = Agent::find(DeviceType::Cpu)?;
+        let dependencies: Vec<bindings::hsa_signal_t> =
+            wait_for.iter().map(|signal| signal.handle()).collect();
+        let done = Signal::create(1)?;
+
+        let mut staging = src.to_vec();
+        let host_ptr = staging.as_mut_ptr() as *mut c_void;
+        let locked_ptr = lock_host_buffer(host_ptr, staging.len(), agent)?;
+
+        // Release the staging copy and forward completion from the runtime's
+        // handler thread. If the copy could not be issued the handler only
+        // cleans up. The handler only receives the staging copy once it is
+        // registered, so a failed registration cannot free it while locked.
+        let staging_slot: Arc<Mutex<Option<Vec<u8>>>> = Arc::new(Mutex::new(None));
+        let handler_staging = Arc::clone(&staging_slot);
+        let issued = Arc::new(AtomicBool::new(true));
+        let handler_issued = Arc::clone(&issued);
+        let done_handle = done.handle();
+        let handler_dependencies = wait_for.to_vec();
+        let cleanup = move |_: i64| unsafe {
+            let staging = handler_staging
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .take();
+            if let Some(mut staging) = staging {
+                bindings::hsa_amd_memory_unlock(staging.as_mut_ptr() as *mut c_void);
+            }
+            drop(Signal::from_raw(done_handle, true));
+            if handler_issued.load(Ordering::SeqCst) {
+                bindings::hsa_signal_subtract_screlease(completion.handle(), 1);
+            }
+            drop(handler_dependencies);
+        };
+        if let Err(error) = done.on_condition(
+            bindings::hsa_signal_condition_t_HSA_SIGNAL_CONDITION_EQ,
+            0,
+            cleanup,
+        ) {
+            unsafe {
+                bindings::hsa_amd_memory_unlock(host_ptr);
+            }
+            drop(staging);
+            return Err(error);
+        }
+        *staging_slot.lock().unwrap_or_else(|e| e.into_inner()) = Some(staging);
+
+        let request = CopyRequest {
+            dst: self.ptr,
+            dst_agent: &self.region.agent,
+            src: locked_ptr,
+            src_agent: &host_agent,
+            size: src.len(),
+            dependencies: &dependencies,
+            completion_signal: &done,
+            engine: CopyEngine::Auto,
+        };
+        let result = request.issue();
+        if result.is_err() {
+            issued.store(false, Ordering::SeqCst);
+            done.store(0);
+        }
+
+        // The handler destroys `done` once it fires.
+        done.into_raw();
+
+        if result.is_ok() {
+            log_debug(&format!(
+                "Started async upload of {} bytes to {:p} after {} dependencies",
+                src.len(),
+                self.ptr,
+                dependencies.len()
+            ));
+        }
+        result
+    }
+}
+
+impl fmt::Debug for Memory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let segment = match self.region.segment() {
+            Ok(bindings::hsa_region_segment_t_HSA_REGION_SEGMENT_GLOBAL) => "global",
+            Ok(bindings::hsa_region_segment_t_HSA_REGION_SEGMENT_READONLY) => "readonly",
+            Ok(bindings::hsa_region_segment_t_HSA_REGION_SEGMENT_PRIVATE) => "private",
+            Ok(bindings::hsa_region_segment_t_HSA_REGION_SEGMENT_GROUP) => "group",
+            Ok(bindings::hsa_region_segment_t_HSA_REGION_SEGMENT_KERNARG) => "kernarg",
+            _ => "unknown",
+        };
+        let flags = self.region.global_flags().ok();
+        let has_flag = |flag: u32| flags.map(|flags| flags & flag != 0);
+
+        f.debug_struct("Memory")
+            .field("ptr", &self.ptr)
+            .field("size", &self.size)
+            .field("region", &format_args!("0x{:x}", self.region.handle.handle))
+            .field("segment", &segment)
+            .field(
+                "fine_grained",
+                &has_flag(bindings::hsa_region_global_flag_t_HSA_REGION_GLOBAL_FLAG_FINE_GRAINED),
+            )
+            .field(
+                "coarse_grained",
+                &has_flag(bindings::hsa_region_global_flag_t_HSA_REGION_GLOBAL_FLAG_COARSE_GRAINED),
+            )
+            .field("host_accessible", &self.is_host_accessible().ok())
+            .finish()
+    }
 }
 
 impl Drop for Memory {
@@ -224,8 +989,12 @@ impl Drop for Memory {
                 "Freeing memory at address {:p} ({} bytes)",
                 self.ptr, self.size
             ));
+            // Forget the allocation before its address can be handed out again
+            #[cfg(feature = "memory-debug")]
+            live_allocations().remove(&(self.ptr as u64));
             unsafe {
                 let status = bindings::hsa_memory_free(self.ptr);
+                TOTAL_ALLOCATED.fetch_sub(self.size, Ordering::Relaxed);
                 if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
                     log_error(&format!(
                         "Failed to free memory: {}",
@@ -237,5 +1006,373 @@ impl Drop for Memory {
     }
 }
 
+pub struct AccessGuard<'a> {
+    memory: &'a Memory,
+}
+
+impl Drop for AccessGuard<'_> {
+    fn drop(&mut self) {
+        let owner = self.memory.region.agent.handle;
+        log_debug(&format!(
+            "Restoring access to memory at {:p} to owning agent 0x{:x}",
+            self.memory.ptr, owner.handle
+        ));
+
+        unsafe {
+            let status =
+                bindings::hsa_amd_agents_allow_access(1, &owner, ptr::null(), self.memory.ptr);
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                log_error(&format!(
+                    "Failed to restore memory access: {}",
+                    HsaError::from_status(status)
+                ));
+            }
+        }
+    }
+}
+
+/// Layout of a pitched 2D copy, see [`Memory::copy_rect`]. All values are
+/// in bytes, except `height` which counts rows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CopyRect {
+    /// Bytes copied from each row.
+    pub width: usize,
+    pub height: usize,
+    /// Distance between the starts of consecutive source rows.
+    pub src_pitch: usize,
+    /// Distance between the starts of consecutive destination rows.
+    pub dst_pitch: usize,
+}
+
+impl CopyRect {
+    fn check_fits(&self, len: usize, pitch: usize, side: &str) -> Result<()> {
+        if self.width > pitch {
+            return Err(HsaError::InvalidArgument(format!(
+                "Row width {} exceeds the {} pitch {}",
+                self.width, side, pitch
+            )));
+        }
+        if self.height == 0 {
+            return Ok(());
+        }
+
+        let needed = (self.height - 1)
+            .checked_mul(pitch)
+            .and_then(|start| start.checked_add(self.width));
+        match needed {
+            Some(needed) if needed <= len => Ok(()),
+            _ => Err(HsaError::InvalidArgument(format!(
+                "{} rows of {} bytes with pitch {} do not fit the {} byte {} buffer",
+                self.height, self.width, pitch, len, side
+            ))),
+        }
+    }
+}
+
+/// Sequential writer over a [`Memory`] allocation.
+///
+/// Each value is placed at the next offset that satisfies its alignment, the
+/// same layout rules a `#[repr(C)]` struct follows, so writing the fields of
+/// a kernel's argument list in order reproduces the kernarg layout.
+pub struct MemoryWriter<'a> {
+    memory: &'a mut Memory,
+    offset: usize,
+}
+
+impl MemoryWriter<'_> {
+    /// Write `value` at the next suitably aligned offset and advance past it.
+    pub fn write<T: Copy>(&mut self, value: &T) -> Result<()> {
+        let size = std::mem::size_of::<T>();
+        let start = self.offset.next_multiple_of(std::mem::align_of::<T>());
+        let end = start
+            .checked_add(size)
+            .filter(|&end| end <= self.memory.size);
+        let Some(end) = end else {
+            return Err(HsaError::InvalidArgument(format!(
+                "Writing {} bytes at offset {} overflows the {} byte allocation",
+                size, start, self.memory.size
+            )));
+        };
+
+        unsafe {
+            let dst = (self.memory.ptr as *mut u8).add(start) as *mut T;
+            ptr::write_unaligned(dst, *value);
+        }
+        self.offset = end;
+        Ok(())
+    }
+
+    /// Offset just past the last value written.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.memory.size - self.offset
+    }
+}
+
+/// A dma-buf file descriptor exported from an allocation, closed through the
+/// runtime on drop.
+#[derive(Debug)]
+pub struct DmaBufFd {
+    fd: c_int,
+    offset: u64,
+}
+
+impl DmaBufFd {
+    pub fn fd(&self) -> c_int {
+        self.fd
+    }
+
+    /// Offset of the allocation within the dma-buf, which may cover a larger
+    /// underlying buffer.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+}
+
+impl AsRawFd for DmaBufFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl Drop for DmaBufFd {
+    fn drop(&mut self) {
+        log_debug(&format!("Closing dma-buf fd {}", self.fd));
+        unsafe {
+            let status = bindings::hsa_amd_portable_close_dmabuf(self.fd);
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                log_error(&format!(
+                    "Failed to close dma-buf fd {}: {}",
+                    self.fd,
+                    HsaError::from_status(status)
+                ));
+            }
+        }
+    }
+}
+
+/// An in-flight asynchronous copy into a page-locked host buffer, which the
+/// guard owns until the copy has completed.
+pub struct AsyncCopy<'a> {
+    signal: Signal,
+    host_ptr: *mut c_void,
+    buffer: Vec<u8>,
+    _source: PhantomData<&'a Memory>,
+}
+
+impl AsyncCopy<'_> {
+    /// Completion signal of the copy; it reaches 0 when the copy is done.
+    pub fn signal(&self) -> &Signal {
+        &self.signal
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.signal.load() == 0
+    }
+
+    /// Block until the copy has completed and return the destination
+    /// buffer, unlocked.
+    pub fn wait(mut self) -> Vec<u8> {
+        self.wait_for_completion();
+        let buffer = std::mem::take(&mut self.buffer);
+        // Dropping the guard unlocks the buffer; its heap allocation is
+        // unaffected by the move above.
+        drop(self);
+        buffer
+    }
+
+    fn wait_for_completion(&self) {
+        while self.signal.wait_eq(0, u64::MAX) != 0 {}
+    }
+}
+
+impl Drop for AsyncCopy<'_> {
+    fn drop(&mut self) {
+        self.wait_for_completion();
+
+        log_debug(&format!("Unlocking host buffer {:p}", self.host_ptr));
+        unsafe {
+            let status = bindings::hsa_amd_memory_unlock(self.host_ptr);
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                log_error(&format!(
+                    "Failed to unlock host buffer: {}",
+                    HsaError::from_status(status)
+                ));
+            }
+        }
+    }
+}
+
+/// Page-lock `size` bytes at `host_ptr` and map them for `agent`, returning
+/// the address `agent` should use to access them.
+fn lock_host_buffer(host_ptr: *mut c_void, size: usize, agent: &Agent) -> Result<*mut c_void> {
+    let mut agent_handle = agent.handle;
+    let mut locked_ptr = ptr::null_mut();
+
+    unsafe {
+        let status =
+            bindings::hsa_amd_memory_lock(host_ptr, size, &mut agent_handle, 1, &mut locked_ptr);
+
+        if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+            let error = HsaError::from_status_with_context(
+                status,
+                &format!("Failed to lock {} byte host buffer at {:p}", size, host_ptr),
+            );
+            log_error(&format!(
+                "Host buffer lock failed: {}",
+                error.full_message()
+            ));
+            return Err(error);
+        }
+    }
+
+    Ok(locked_ptr)
+}
+
+/// Read from `reader` until `buf` is full or the input ends, returning the
+/// number of bytes read.
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// Bitwise CRC-32 with the reflected IEEE polynomial, as used by zlib.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+/// Which hardware path an asynchronous copy should take.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CopyEngine {
+    /// Let the runtime choose. It uses blit (compute shader) kernels for
+    /// copies within one agent; setting `HSA_ENABLE_SDMA=0` makes it use
+    /// them for every copy.
+    #[default]
+    Auto,
+    /// Force the copy onto an SDMA engine, using the first engine the runtime
+    /// reports as available between the two agents.
+    Sdma,
+}
+
+/// Parameters of one `hsa_amd_memory_async_copy` call.
+struct CopyRequest<'a> {
+    dst: *mut c_void,
+    dst_agent: &'a Agent,
+    src: *const c_void,
+    src_agent: &'a Agent,
+    size: usize,
+    /// Signals that must all reach 0 before the copy starts.
+    dependencies: &'a [bindings::hsa_signal_t],
+    completion_signal: &'a Signal,
+    engine: CopyEngine,
+}
+
+impl CopyRequest<'_> {
+    fn issue(&self) -> Result<()> {
+        let num_deps = self.dependencies.len() as u32;
+        let deps = if self.dependencies.is_empty() {
+            ptr::null()
+        } else {
+            self.dependencies.as_ptr()
+        };
+
+        unsafe {
+            let status = match self.engine {
+                CopyEngine::Auto => bindings::hsa_amd_memory_async_copy(
+                    self.dst,
+                    self.dst_agent.handle,
+                    self.src,
+                    self.src_agent.handle,
+                    self.size,
+                    num_deps,
+                    deps,
+                    self.completion_signal.handle(),
+                ),
+                CopyEngine::Sdma => {
+                    let engine_id = first_sdma_engine(self.dst_agent, self.src_agent)?;
+                    log_debug(&format!(
+                        "Forcing async copy onto SDMA engine 0x{:x}",
+                        engine_id
+                    ));
+                    bindings::hsa_amd_memory_async_copy_on_engine(
+                        self.dst,
+                        self.dst_agent.handle,
+                        self.src,
+                        self.src_agent.handle,
+                        self.size,
+                        num_deps,
+                        deps,
+                        self.completion_signal.handle(),
+                        engine_id,
+                        true,
+                    )
+                }
+            };
+
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                let error = HsaError::from_status_with_context(
+                    status,
+                    &format!("Failed to start async copy of {} bytes", self.size),
+                );
+                log_error(&format!("Async copy failed: {}", error.full_message()));
+                return Err(error);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Lowest-numbered SDMA engine currently available for copies from
+/// `src_agent` to `dst_agent`.
+fn first_sdma_engine(
+    dst_agent: &Agent,
+    src_agent: &Agent,
+) -> Result<bindings::hsa_amd_sdma_engine_id_t> {
+    let mut engine_mask = 0u32;
+
+    unsafe {
+        let status = bindings::hsa_amd_memory_copy_engine_status(
+            dst_agent.handle,
+            src_agent.handle,
+            &mut engine_mask,
+        );
+
+        if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+            return Err(HsaError::from_status_with_context(
+                status,
+                "Failed to query SDMA engine status",
+            ));
+        }
+    }
+
+    if engine_mask == 0 {
+        return Err(HsaError::OutOfResources(
+            "No SDMA engine available between the copy agents".to_string(),
+        ));
+    }
+
+    // Engine ids are single-bit flags, matching the bits of the status mask.
+    Ok(1 << engine_mask.trailing_zeros())
+}
+
 unsafe impl Send for Memory {}
 unsafe impl Sync for Memory {}
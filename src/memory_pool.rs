@@ -0,0 +1,170 @@
+use crate::bindings;
+use crate::error::log_debug;
+use crate::{Agent, HsaError, Result};
+use std::hash::{Hash, Hasher};
+use std::os::raw::c_void;
+
+/// An AMD memory pool, the `hsa_amd_memory_pool_t` counterpart of
+/// [`MemoryRegion`](crate::MemoryRegion).
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryPool {
+    pub(crate) handle: bindings::hsa_amd_memory_pool_t,
+    pub(crate) agent: Agent,
+}
+
+/// How an agent may access a memory pool it does not own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PoolAccess {
+    /// The agent can never access allocations from the pool.
+    Never,
+    /// The agent can access allocations from the pool without further setup.
+    Allowed,
+    /// The agent can access allocations only after they are explicitly made
+    /// accessible to it, e.g. with `hsa_amd_agents_allow_access`.
+    Disallowed,
+}
+
+impl PartialEq for MemoryPool {
+    fn eq(&self, other: &Self) -> bool {
+        self.handle.handle == other.handle.handle
+    }
+}
+
+impl Eq for MemoryPool {}
+
+impl Hash for MemoryPool {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.handle.handle.hash(state);
+    }
+}
+
+impl MemoryPool {
+    /// The agent this pool was discovered through.
+    pub fn agent(&self) -> Agent {
+        self.agent
+    }
+
+    pub fn segment(&self) -> Result<bindings::hsa_amd_segment_t> {
+        let mut segment = 0u32;
+        unsafe {
+            let status = bindings::hsa_amd_memory_pool_get_info(
+                self.handle,
+                bindings::hsa_amd_memory_pool_info_t_HSA_AMD_MEMORY_POOL_INFO_SEGMENT,
+                &mut segment as *mut _ as *mut c_void,
+            );
+
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                return Err(HsaError::from_status_with_context(
+                    status,
+                    "Failed to get memory pool segment",
+                ));
+            }
+        }
+        Ok(segment)
+    }
+
+    pub fn global_flags(&self) -> Result<u32> {
+        let mut flags = 0u32;
+        unsafe {
+            let status = bindings::hsa_amd_memory_pool_get_info(
+                self.handle,
+                bindings::hsa_amd_memory_pool_info_t_HSA_AMD_MEMORY_POOL_INFO_GLOBAL_FLAGS,
+                &mut flags as *mut _ as *mut c_void,
+            );
+
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                return Err(HsaError::from_status_with_context(
+                    status,
+                    "Failed to get memory pool global flags",
+                ));
+            }
+        }
+        Ok(flags)
+    }
+
+    pub fn size(&self) -> Result<usize> {
+        let mut size = 0usize;
+        unsafe {
+            let status = bindings::hsa_amd_memory_pool_get_info(
+                self.handle,
+                bindings::hsa_amd_memory_pool_info_t_HSA_AMD_MEMORY_POOL_INFO_SIZE,
+                &mut size as *mut _ as *mut c_void,
+            );
+
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                return Err(HsaError::from_status_with_context(
+                    status,
+                    "Failed to get memory pool size",
+                ));
+            }
+        }
+        Ok(size)
+    }
+
+    /// Granularity of allocations from this pool, and of the offsets and
+    /// lengths used with [`VirtualMemory`](crate::VirtualMemory).
+    pub fn alloc_granule(&self) -> Result<usize> {
+        let mut granule = 0usize;
+        unsafe {
+            let status = bindings::hsa_amd_memory_pool_get_info(
+                self.handle,
+                bindings::hsa_amd_memory_pool_info_t_HSA_AMD_MEMORY_POOL_INFO_RUNTIME_ALLOC_GRANULE,
+                &mut granule as *mut _ as *mut c_void,
+            );
+
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                return Err(HsaError::from_status_with_context(
+                    status,
+                    "Failed to get memory pool allocation granule",
+                ));
+            }
+        }
+        Ok(granule)
+    }
+
+    /// How `agent` may access allocations from this pool. Querying a peer
+    /// GPU tells whether it can read and write this pool directly.
+    pub fn accessible_by(&self, agent: &Agent) -> Result<PoolAccess> {
+        let mut access =
+            bindings::hsa_amd_memory_pool_access_t_HSA_AMD_MEMORY_POOL_ACCESS_NEVER_ALLOWED;
+        unsafe {
+            let status = bindings::hsa_amd_agent_memory_pool_get_info(
+                agent.handle,
+                self.handle,
+                bindings::hsa_amd_agent_memory_pool_info_t_HSA_AMD_AGENT_MEMORY_POOL_INFO_ACCESS,
+                &mut access as *mut _ as *mut c_void,
+            );
+
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                return Err(HsaError::from_status_with_context(
+                    status,
+                    "Failed to get memory pool access for agent",
+                ));
+            }
+        }
+
+        let access = match access {
+            bindings::hsa_amd_memory_pool_access_t_HSA_AMD_MEMORY_POOL_ACCESS_NEVER_ALLOWED => {
+                PoolAccess::Never
+            }
+            bindings::hsa_amd_memory_pool_access_t_HSA_AMD_MEMORY_POOL_ACCESS_ALLOWED_BY_DEFAULT => {
+                PoolAccess::Allowed
+            }
+            bindings::hsa_amd_memory_pool_access_t_HSA_AMD_MEMORY_POOL_ACCESS_DISALLOWED_BY_DEFAULT => {
+                PoolAccess::Disallowed
+            }
+            other => {
+                return Err(HsaError::InvalidArgument(format!(
+                    "Unknown memory pool access value: {}",
+                    other
+                )));
+            }
+        };
+
+        log_debug(&format!(
+            "Memory pool 0x{:x} access for agent 0x{:x}: {:?}",
+            self.handle.handle, agent.handle.handle, access
+        ));
+        Ok(access)
+    }
+}
@@ -1,17 +1,158 @@
+use crate::agent::LaunchLimits;
 use crate::bindings;
-use crate::error::{log_debug, log_error, log_info};
-use crate::{Agent, HsaError, Result};
+use crate::error::{log_debug, log_error, log_info, log_warning};
+use crate::executable::{FenceScope, make_dispatch_header};
+use crate::{Agent, HsaError, Result, SignalPool};
+use bitflags::bitflags;
+use std::any::Any;
+use std::collections::HashMap;
+use std::mem::ManuallyDrop;
+use std::os::raw::c_void;
 use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, LazyLock, Mutex, MutexGuard, OnceLock};
 
 pub struct Queue {
     ptr: *mut bindings::hsa_queue_t,
+    owned: bool,
+    requested_size: u32,
+    agent: Option<Agent>,
+    /// The agent's dispatch limits, queried on first dispatch.
+    limits: OnceLock<LaunchLimits>,
+    /// Passed to the runtime as the error callback's data; `None` for
+    /// queues wrapped with [`Queue::from_raw`].
+    error_state: Option<Box<ErrorState>>,
+}
+
+type UserData = Arc<dyn Any + Send + Sync>;
+type ErrorCallback = Arc<dyn Fn(&QueueError) + Send + Sync>;
+
+/// What the runtime's queue error callback reaches through its `data`
+/// argument. Handed to `hsa_queue_create`, so it is in place before the
+/// runtime can report anything, and boxed so it does not move with the
+/// queue.
+struct ErrorState {
+    callback: Option<ErrorCallback>,
+    /// Set once the runtime has reported an error for the queue.
+    failed: AtomicBool,
+}
+
+/// Data attached to a queue with [`Queue::set_user_data`], keyed by queue
+/// id so that the error callback can find it.
+struct QueueEntry {
+    user_data: Option<UserData>,
+}
+
+static QUEUE_REGISTRY: LazyLock<Mutex<HashMap<u64, QueueEntry>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn registry() -> MutexGuard<'static, HashMap<u64, QueueEntry>> {
+    QUEUE_REGISTRY.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// An asynchronous error reported by the runtime for a queue, passed to the
+/// callback given to [`Queue::create_with_error_callback`].
+pub struct QueueError {
+    pub queue_id: u64,
+    pub error: HsaError,
+    user_data: Option<UserData>,
+}
+
+impl QueueError {
+    /// The data attached to the queue with [`Queue::set_user_data`], if it
+    /// is of type `T`.
+    pub fn user_data<T: Any + Send + Sync>(&self) -> Option<&T> {
+        self.user_data.as_deref()?.downcast_ref()
+    }
+}
+
+bitflags! {
+    /// Packet types a queue accepts, decoded from `hsa_queue_t::features`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct QueueFeatures: u32 {
+        const KERNEL_DISPATCH = bindings::hsa_queue_feature_t_HSA_QUEUE_FEATURE_KERNEL_DISPATCH;
+        const AGENT_DISPATCH = bindings::hsa_queue_feature_t_HSA_QUEUE_FEATURE_AGENT_DISPATCH;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueType {
+    Single,
+    Multi,
+    Cooperative,
+}
+
+impl QueueType {
+    pub(crate) fn raw(self) -> bindings::hsa_queue_type_t {
+        match self {
+            QueueType::Single => bindings::hsa_queue_type_t_HSA_QUEUE_TYPE_SINGLE,
+            QueueType::Multi => bindings::hsa_queue_type_t_HSA_QUEUE_TYPE_MULTI,
+            QueueType::Cooperative => bindings::hsa_queue_type_t_HSA_QUEUE_TYPE_COOPERATIVE,
+        }
+    }
 }
 
 impl Queue {
     pub fn create(agent: &Agent, size: u32) -> Result<Self> {
+        Self::create_with_type(agent, size, QueueType::Multi, None)
+    }
+
+    /// Create a queue whose asynchronous errors (e.g. a malformed packet or
+    /// a memory fault in a kernel) are passed to `callback`.
+    ///
+    /// The callback runs on a runtime thread and can reach whatever was
+    /// attached with [`Queue::set_user_data`] through
+    /// [`QueueError::user_data`]. The queue is unusable once an error has
+    /// been reported until it is [reset](Queue::reset).
+    pub fn create_with_error_callback<F>(agent: &Agent, size: u32, callback: F) -> Result<Self>
+    where
+        F: Fn(&QueueError) + Send + Sync + 'static,
+    {
+        Self::create_with_type(agent, size, QueueType::Multi, Some(Arc::new(callback)))
+    }
+
+    /// Create a queue together with a [`SignalPool`] of `pool_size`
+    /// completion signals, sized for the number of dispatches expected to
+    /// be in flight at once. The pool's signals can be waited on by the host
+    /// and by `agent`.
+    pub fn create_with_signal_pool(
+        agent: &Agent,
+        size: u32,
+        pool_size: usize,
+    ) -> Result<(Self, SignalPool)> {
+        let queue = Self::create(agent, size)?;
+        let pool = SignalPool::new(agent, pool_size)?;
+        Ok((queue, pool))
+    }
+
+    /// Create a queue for cooperative (grid-synchronising) kernels.
+    ///
+    /// Every workgroup of a cooperative dispatch must be resident on the
+    /// device at the same time so they can synchronise across the grid. The
+    /// grid is therefore bounded by what the agent's compute units can hold
+    /// at once, which depends on the kernel's register, LDS and scratch
+    /// usage; a grid that does not fit will never complete. Cooperative
+    /// dispatches also occupy the CUs exclusively while they run, so other
+    /// queues on the device make no progress on those CUs in the meantime.
+    pub fn create_cooperative(agent: &Agent, size: u32) -> Result<Self> {
+        if !agent.supports_cooperative_dispatch()? {
+            return Err(HsaError::QueueCreationFailed(format!(
+                "Agent 0x{:x} does not support cooperative queues",
+                agent.handle.handle
+            )));
+        }
+        Self::create_with_type(agent, size, QueueType::Cooperative, None)
+    }
+
+    fn create_with_type(
+        agent: &Agent,
+        size: u32,
+        queue_type: QueueType,
+        error_callback: Option<ErrorCallback>,
+    ) -> Result<Self> {
         log_info(&format!(
-            "Creating queue with size {} for agent 0x{:x}",
-            size, agent.handle.handle
+            "Creating {:?} queue with size {} for agent 0x{:x}",
+            queue_type, size, agent.handle.handle
         ));
 
         // Validate size is power of 2
@@ -41,14 +182,20 @@ impl Queue {
         }
 
         let mut queue_ptr = ptr::null_mut();
+        let error_state = Box::new(ErrorState {
+            callback: error_callback,
+            failed: AtomicBool::new(false),
+        });
 
         unsafe {
             let status = bindings::hsa_queue_create(
                 agent.handle,
                 size,
-                bindings::hsa_queue_type_t_HSA_QUEUE_TYPE_MULTI,
-                None,
-                ptr::null_mut(),
+                queue_type.raw(),
+                // Installed even without a user callback so that errors are
+                // recorded for `Queue::has_error`
+                Some(queue_error_trampoline),
+                &*error_state as *const ErrorState as *mut c_void,
                 0,
                 0,
                 &mut queue_ptr,
@@ -62,8 +209,8 @@ impl Queue {
                         size, agent.handle.handle
                     ),
                 );
-                log_error(&format!("Queue creation failed: {}", error));
-                return Err(HsaError::QueueCreationFailed(error.to_string()));
+                log_error(&format!("Queue creation failed: {}", error.full_message()));
+                return Err(HsaError::QueueCreationFailed(error.full_message()));
             }
         }
 
@@ -73,9 +220,22 @@ impl Queue {
             ));
         }
 
-        let queue = Queue { ptr: queue_ptr };
-        let actual_size = queue.get().size;
+        let queue = Queue {
+            ptr: queue_ptr,
+            owned: true,
+            requested_size: size,
+            agent: Some(*agent),
+            limits: OnceLock::new(),
+            error_state: Some(error_state),
+        };
+        let actual_size = queue.get_size();
 
+        if actual_size != size {
+            log_warning(&format!(
+                "Queue size rounded up by runtime: requested {}, actual {}",
+                size, actual_size
+            ));
+        }
         log_info(&format!(
             "Queue created successfully: requested size {}, actual size {}",
             size, actual_size
@@ -85,6 +245,57 @@ impl Queue {
         Ok(queue)
     }
 
+    /// Wrap a queue created outside this crate.
+    ///
+    /// If `owned` is true the queue is destroyed when the wrapper is dropped;
+    /// otherwise whoever created it stays responsible for destroying it.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to a live queue returned by `hsa_queue_create` (or
+    /// an equivalent runtime call) that outlives the returned wrapper. When
+    /// `owned` is true nothing else may destroy it.
+    pub unsafe fn from_raw(ptr: *mut bindings::hsa_queue_t, owned: bool) -> Self {
+        log_debug(&format!("Wrapping raw queue {:p} (owned: {})", ptr, owned));
+        let requested_size = unsafe { (*ptr).size };
+        Queue {
+            ptr,
+            owned,
+            requested_size,
+            agent: None,
+            limits: OnceLock::new(),
+            error_state: None,
+        }
+    }
+
+    /// Release the queue pointer without destroying the queue. The caller
+    /// becomes responsible for calling `hsa_queue_destroy` on it.
+    pub fn into_raw(self) -> *mut bindings::hsa_queue_t {
+        // The error state is leaked along with the wrapper: the runtime keeps
+        // passing it to the error callback for as long as the queue lives.
+        let queue = ManuallyDrop::new(self);
+        queue.ptr
+    }
+
+    /// The agent the queue was created on, or `None` for queues wrapped with
+    /// [`Queue::from_raw`].
+    pub fn agent(&self) -> Option<Agent> {
+        self.agent
+    }
+
+    /// Dispatch limits of the queue's agent, queried once and then cached,
+    /// or `None` if the agent is not known.
+    pub(crate) fn launch_limits(&self) -> Result<Option<&LaunchLimits>> {
+        let Some(agent) = self.agent else {
+            return Ok(None);
+        };
+        if let Some(limits) = self.limits.get() {
+            return Ok(Some(limits));
+        }
+        let limits = agent.launch_limits()?;
+        Ok(Some(self.limits.get_or_init(|| limits)))
+    }
+
     pub fn as_ptr(&self) -> *mut bindings::hsa_queue_t {
         self.ptr
     }
@@ -104,6 +315,12 @@ impl Queue {
         old_index
     }
 
+    /// Overwrite the write index.
+    ///
+    /// This is only meaningful for a queue with a single producer; with
+    /// concurrent producers it can move the index backwards over slots
+    /// another thread has claimed. Kernel dispatch and [`Queue::reserve`]
+    /// never call it.
     pub fn store_write_index(&self, value: u64) {
         log_debug(&format!("Storing write index: {}", value));
         unsafe {
@@ -123,18 +340,242 @@ impl Queue {
         index
     }
 
+    /// Reserve `count` consecutive packet slots for in-place writes.
+    ///
+    /// Blocks until the packet processor has drained enough of the ring for
+    /// the reserved slots to be free. The slots are handed out with an invalid
+    /// header, so nothing is visible to the packet processor until
+    /// [`PacketReservation::commit`] publishes the headers and rings the
+    /// doorbell once for the whole batch. A reservation dropped without being
+    /// committed is filled with no-op barrier packets so the ring keeps moving.
+    pub fn reserve(&self, count: u64) -> Result<PacketReservation<'_>> {
+        let size = self.get_size() as u64;
+        if count == 0 || count > size {
+            return Err(HsaError::InvalidArgument(format!(
+                "Cannot reserve {} packets in a queue of size {}",
+                count, size
+            )));
+        }
+
+        let first_index = self.claim_slots(count);
+        for i in 0..count {
+            clear_packet(self.packet_slot(first_index + i));
+        }
+
+        log_debug(&format!(
+            "Reserved {} packets starting at index {}",
+            count, first_index
+        ));
+        Ok(PacketReservation {
+            queue: self,
+            first_index,
+            count,
+            committed: false,
+        })
+    }
+
+    /// Claim a single packet slot without blocking.
+    ///
+    /// The write index is advanced with a compare-and-swap, and only if the
+    /// ring has a free slot, so this never moves the index past what the
+    /// packet processor has drained. Returns the claimed packet index, whose
+    /// slot has been given an invalid header, or `None` if the queue is full.
+    pub fn try_reserve(&self) -> Option<u64> {
+        let size = self.get_size() as u64;
+
+        loop {
+            let write_index = unsafe { bindings::hsa_queue_load_write_index_scacquire(self.ptr) };
+            let read_index = unsafe { bindings::hsa_queue_load_read_index_scacquire(self.ptr) };
+            if write_index - read_index >= size {
+                log_debug(&format!(
+                    "Queue full (read index: {}, write index: {})",
+                    read_index, write_index
+                ));
+                return None;
+            }
+
+            let observed = unsafe {
+                bindings::hsa_queue_cas_write_index_scacq_screl(
+                    self.ptr,
+                    write_index,
+                    write_index + 1,
+                )
+            };
+            if observed == write_index {
+                clear_packet(self.packet_slot(write_index));
+                log_debug(&format!("Reserved packet at index {}", write_index));
+                return Some(write_index);
+            }
+
+            std::hint::spin_loop();
+        }
+    }
+
+    /// Claim `count` consecutive packet indices and wait until the packet
+    /// processor has consumed whatever previously occupied those slots.
+    ///
+    /// The write index is only ever advanced with an atomic add, so concurrent
+    /// producers each get a disjoint range of slots. Every slot is later made
+    /// visible on its own by publishing its header, and the packet processor
+    /// stops at the first slot whose header is still invalid, so packets
+    /// published out of order are consumed in index order. This is what makes
+    /// it safe for several threads to submit to the same queue.
+    pub(crate) fn claim_slots(&self, count: u64) -> u64 {
+        let size = self.get_size() as u64;
+        let first_index = self.add_write_index(count);
+
+        while first_index + count
+            - unsafe { bindings::hsa_queue_load_read_index_scacquire(self.ptr) }
+            > size
+        {
+            std::hint::spin_loop();
+        }
+
+        first_index
+    }
+
+    pub(crate) fn packet_slot(&self, index: u64) -> *mut bindings::hsa_kernel_dispatch_packet_t {
+        let queue = self.get();
+        let slot = index % queue.size as u64;
+        unsafe {
+            (queue.base_address as *mut bindings::hsa_kernel_dispatch_packet_t).add(slot as usize)
+        }
+    }
+
+    /// Notify the packet processor that packets up to `index` are published.
+    pub(crate) fn ring_doorbell(&self, index: u64) {
+        unsafe {
+            bindings::hsa_signal_store_screlease(self.get().doorbell_signal, index as i64);
+        }
+        log_debug(&format!("Doorbell rung with packet ID: {}", index));
+    }
+
     pub fn get_id(&self) -> u64 {
         self.get().id
     }
 
+    /// Attach application data to the queue, replacing any set before. The
+    /// error callback reaches it through [`QueueError::user_data`].
+    pub fn set_user_data<T: Any + Send + Sync>(&self, data: T) {
+        registry()
+            .entry(self.get_id())
+            .or_insert(QueueEntry { user_data: None })
+            .user_data = Some(Arc::new(data));
+    }
+
+    /// The data attached with [`Queue::set_user_data`], if it is of type `T`.
+    pub fn user_data<T: Any + Send + Sync>(&self) -> Option<Arc<T>> {
+        let registry = registry();
+        let data = registry.get(&self.get_id())?.user_data.clone()?;
+        data.downcast().ok()
+    }
+
+    /// Number of packet slots in the ring, as allocated by the runtime.
+    ///
+    /// This can be larger than the size passed to [`Queue::create`], so ring
+    /// arithmetic (slot wrapping, free-space checks) must use this value
+    /// rather than the requested one.
     pub fn get_size(&self) -> u32 {
         self.get().size
     }
 
+    /// The size passed when the queue was created. For queues wrapped with
+    /// [`Queue::from_raw`] this is the actual ring size.
+    pub fn requested_size(&self) -> u32 {
+        self.requested_size
+    }
+
     pub fn get_type(&self) -> u32 {
         self.get().type_
     }
 
+    pub fn queue_type(&self) -> Result<QueueType> {
+        match self.get_type() {
+            bindings::hsa_queue_type_t_HSA_QUEUE_TYPE_SINGLE => Ok(QueueType::Single),
+            bindings::hsa_queue_type_t_HSA_QUEUE_TYPE_MULTI => Ok(QueueType::Multi),
+            bindings::hsa_queue_type_t_HSA_QUEUE_TYPE_COOPERATIVE => Ok(QueueType::Cooperative),
+            other => Err(HsaError::InvalidArgument(format!(
+                "Unknown queue type: {}",
+                other
+            ))),
+        }
+    }
+
+    /// Whether the runtime has reported an asynchronous error for this
+    /// queue, e.g. after a malformed packet. Such a queue processes no more
+    /// packets; see [`Queue::reset`]. Always false for queues wrapped with
+    /// [`Queue::from_raw`], whose errors go to their creator's callback.
+    pub fn has_error(&self) -> bool {
+        self.error_state
+            .as_ref()
+            .is_some_and(|state| state.failed.load(Ordering::Acquire))
+    }
+
+    /// Replace the underlying hardware queue with a fresh one of the same
+    /// agent, size and type, e.g. to recover from an error reported by the
+    /// runtime. The error callback and user data carry over.
+    ///
+    /// Packets still in the old queue are discarded, so completion signals
+    /// of dispatches that had not finished are never decremented. Only
+    /// queues created by this crate can be reset.
+    pub fn reset(&mut self) -> Result<()> {
+        let Some(agent) = self.agent.filter(|_| self.owned) else {
+            return Err(HsaError::Unsupported(
+                "only queues created by this crate can be reset".to_string(),
+            ));
+        };
+        let old_id = self.get_id();
+        log_info(&format!("Resetting queue {}", old_id));
+
+        let queue_type = self.queue_type()?;
+        if let Err(e) = self.inactivate() {
+            log_warning(&format!(
+                "Inactivating queue {} before reset failed: {}",
+                old_id,
+                e.full_message()
+            ));
+        }
+
+        let callback = self
+            .error_state
+            .as_ref()
+            .and_then(|state| state.callback.clone());
+        let replacement =
+            Self::create_with_type(&agent, self.requested_size, queue_type, callback)?;
+        let new_id = replacement.get_id();
+        {
+            let mut registry = registry();
+            if let Some(entry) = registry.remove(&old_id) {
+                registry.insert(new_id, entry);
+            }
+        }
+
+        // Dropping the old wrapper destroys the old hardware queue
+        drop(std::mem::replace(self, replacement));
+
+        log_info(&format!("Queue {} replaced by queue {}", old_id, new_id));
+        Ok(())
+    }
+
+    /// The packet types this queue accepts. Bits the crate does not know
+    /// are kept as they are.
+    pub fn features(&self) -> QueueFeatures {
+        QueueFeatures::from_bits_retain(self.get().features)
+    }
+
+    /// Fail unless the queue accepts kernel dispatch packets; writing one to
+    /// an agent-dispatch-only queue would fault instead of reporting an
+    /// error.
+    pub(crate) fn check_kernel_dispatch(&self) -> Result<()> {
+        if !self.features().contains(QueueFeatures::KERNEL_DISPATCH) {
+            return Err(HsaError::InvalidArgument(format!(
+                "Queue does not support kernel dispatch packets (features: {:?})",
+                self.features()
+            )));
+        }
+        Ok(())
+    }
+
     pub fn inactivate(&self) -> Result<()> {
         log_info("Inactivating queue");
         unsafe {
@@ -142,7 +583,10 @@ impl Queue {
             if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
                 let error =
                     HsaError::from_status_with_context(status, "Failed to inactivate queue");
-                log_error(&format!("Queue inactivation failed: {}", error));
+                log_error(&format!(
+                    "Queue inactivation failed: {}",
+                    error.full_message()
+                ));
                 return Err(error);
             }
         }
@@ -150,51 +594,274 @@ impl Queue {
         Ok(())
     }
 
+    /// Snapshot of the queue's static properties and current occupancy.
+    ///
+    /// The read index is loaded before the write index, both with acquire
+    /// semantics. Both only ever grow, so the snapshot never shows fewer
+    /// pending packets than were pending when the read index was sampled.
+    pub fn info(&self) -> QueueInfo {
+        let queue_ref = self.get();
+        let read_index = unsafe { bindings::hsa_queue_load_read_index_scacquire(self.ptr) };
+        let write_index = unsafe { bindings::hsa_queue_load_write_index_scacquire(self.ptr) };
+        let pending = write_index.wrapping_sub(read_index);
+
+        QueueInfo {
+            id: queue_ref.id,
+            size: queue_ref.size,
+            queue_type: self.queue_type().ok(),
+            raw_type: queue_ref.type_,
+            features: queue_ref.features,
+            read_index,
+            write_index,
+            pending,
+            utilization: pending as f64 / queue_ref.size as f64,
+        }
+    }
+
     pub fn print_info(&self) {
         let queue_ref = self.get();
+        let info = self.info();
         log_info(&format!("Queue Information:"));
-        log_info(&format!("  ID: {}", queue_ref.id));
-        log_info(&format!("  Size: {}", queue_ref.size));
-        log_info(&format!("  Type: {}", queue_ref.type_));
-        log_info(&format!("  Features: 0x{:x}", queue_ref.features));
+        log_info(&format!("  ID: {}", info.id));
+        log_info(&format!("  Size: {}", info.size));
+        match info.queue_type {
+            Some(queue_type) => log_info(&format!("  Type: {:?}", queue_type)),
+            None => log_info(&format!("  Type: {} (unknown)", info.raw_type)),
+        }
+        log_info(&format!(
+            "  Features: {:?} (0x{:x})",
+            self.features(),
+            info.features
+        ));
         log_info(&format!("  Base Address: {:p}", queue_ref.base_address));
         log_info(&format!(
             "  Doorbell Signal: 0x{:x}",
             queue_ref.doorbell_signal.handle
         ));
 
-        // Show current queue state
-        let read_idx = self.load_read_index();
-        let write_idx = self.load_write_index();
-        let pending = write_idx.wrapping_sub(read_idx);
-
         log_info(&format!("  Current State:"));
-        log_info(&format!("    Read Index: {}", read_idx));
-        log_info(&format!("    Write Index: {}", write_idx));
-        log_info(&format!("    Pending Packets: {}", pending));
+        log_info(&format!("    Read Index: {}", info.read_index));
+        log_info(&format!("    Write Index: {}", info.write_index));
+        log_info(&format!("    Pending Packets: {}", info.pending));
         log_info(&format!(
             "    Utilization: {:.1}%",
-            (pending as f64 / queue_ref.size as f64) * 100.0
+            info.utilization * 100.0
         ));
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QueueInfo {
+    pub id: u64,
+    pub size: u32,
+    /// `None` if the runtime reported a type this crate does not know.
+    pub queue_type: Option<QueueType>,
+    pub raw_type: u32,
+    pub features: u32,
+    pub read_index: u64,
+    pub write_index: u64,
+    /// Packets written but not yet consumed by the packet processor.
+    pub pending: u64,
+    /// `pending / size`, as a fraction.
+    pub utilization: f64,
+}
+
+pub struct PacketReservation<'a> {
+    queue: &'a Queue,
+    first_index: u64,
+    count: u64,
+    committed: bool,
+}
+
+impl PacketReservation<'_> {
+    pub fn len(&self) -> u64 {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    pub fn first_index(&self) -> u64 {
+        self.first_index
+    }
+
+    /// Mutable access to the `index`-th reserved packet.
+    ///
+    /// The `header` field is written by `commit`; anything stored there
+    /// directly is overwritten.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is not less than the number of reserved packets.
+    pub fn slot_mut(&mut self, index: u64) -> &mut bindings::hsa_kernel_dispatch_packet_t {
+        assert!(
+            index < self.count,
+            "packet index {} out of range for reservation of {}",
+            index,
+            self.count
+        );
+        let packet = self.slot_ptr(index);
+        #[cfg(feature = "testing")]
+        crate::testing::notify(packet, crate::testing::PacketWrite::Body);
+        unsafe { &mut *packet }
+    }
+
+    /// Publish every reserved packet as a kernel dispatch with system-scope
+    /// fences and ring the doorbell once.
+    pub fn commit(self) {
+        self.commit_with_headers(|_| make_dispatch_header(FenceScope::System, FenceScope::System));
+    }
+
+    /// Publish the reserved packets using `header(i)` as the header of the
+    /// `i`-th packet, then ring the doorbell once.
+    pub fn commit_with_headers(mut self, mut header: impl FnMut(u64) -> u16) {
+        for i in 0..self.count {
+            publish_packet(self.slot_ptr(i), header(i));
+        }
+        self.ring_doorbell();
+        self.committed = true;
+
+        log_debug(&format!(
+            "Committed {} packets starting at index {}",
+            self.count, self.first_index
+        ));
+    }
+
+    fn slot_ptr(&self, index: u64) -> *mut bindings::hsa_kernel_dispatch_packet_t {
+        self.queue.packet_slot(self.first_index + index)
+    }
+
+    fn ring_doorbell(&self) {
+        self.queue.ring_doorbell(self.first_index + self.count - 1);
+    }
+}
+
+impl Drop for PacketReservation<'_> {
+    fn drop(&mut self) {
+        if self.committed {
+            return;
+        }
+
+        log_warning(&format!(
+            "Packet reservation of {} packets dropped without commit; submitting no-op barriers",
+            self.count
+        ));
+
+        let barrier_header = (bindings::hsa_packet_type_t_HSA_PACKET_TYPE_BARRIER_AND as u16)
+            << bindings::hsa_packet_header_t_HSA_PACKET_HEADER_TYPE;
+        // A barrier-AND with no dependency or completion signals completes
+        // immediately.
+        let body: bindings::hsa_kernel_dispatch_packet_t = unsafe { std::mem::zeroed() };
+        for i in 0..self.count {
+            let packet = self.slot_ptr(i);
+            write_packet_body(packet, &body);
+            publish_packet(packet, barrier_header);
+        }
+        self.ring_doorbell();
+    }
+}
+
+fn invalid_packet_header() -> u16 {
+    (bindings::hsa_packet_type_t_HSA_PACKET_TYPE_INVALID as u16)
+        << bindings::hsa_packet_header_t_HSA_PACKET_HEADER_TYPE
+}
+
+/// Mark a claimed slot invalid and zero everything after the header, so the
+/// packet can be filled in without the packet processor picking it up early.
+pub(crate) fn clear_packet(packet: *mut bindings::hsa_kernel_dispatch_packet_t) {
+    unsafe {
+        (*packet).header = invalid_packet_header();
+        ptr::write_bytes((packet as *mut u8).add(2), 0, 62);
+    }
+    #[cfg(feature = "testing")]
+    crate::testing::notify(packet, crate::testing::PacketWrite::Cleared);
+}
+
+/// Copy everything after the header of `body` into the slot at `packet`. The
+/// slot's header is left alone, so the packet stays invisible until
+/// [`publish_packet`].
+pub(crate) fn write_packet_body(
+    packet: *mut bindings::hsa_kernel_dispatch_packet_t,
+    body: &bindings::hsa_kernel_dispatch_packet_t,
+) {
+    unsafe {
+        ptr::copy_nonoverlapping(
+            (body as *const _ as *const u8).add(2),
+            (packet as *mut u8).add(2),
+            62,
+        );
+    }
+    #[cfg(feature = "testing")]
+    crate::testing::notify(packet, crate::testing::PacketWrite::Body);
+}
+
+/// Make a fully written packet visible to the packet processor by storing its
+/// header and setup words together with release semantics.
+pub(crate) fn publish_packet(packet: *mut bindings::hsa_kernel_dispatch_packet_t, header: u16) {
+    unsafe {
+        let setup = (*packet).setup;
+        let word = header as u32 | (setup as u32) << 16;
+        AtomicU32::from_ptr(packet as *mut u32).store(word, Ordering::Release);
+    }
+    #[cfg(feature = "testing")]
+    crate::testing::notify(packet, crate::testing::PacketWrite::Header { header });
+}
+
 impl Drop for Queue {
     fn drop(&mut self) {
-        if !self.ptr.is_null() {
-            log_debug("Destroying queue");
-            unsafe {
-                let status = bindings::hsa_queue_destroy(self.ptr);
-                if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
-                    log_error(&format!(
-                        "Failed to destroy queue: {}",
-                        HsaError::from_status(status)
-                    ));
-                }
+        if self.ptr.is_null() {
+            return;
+        }
+        registry().remove(&self.get_id());
+        if !self.owned {
+            return;
+        }
+
+        log_debug("Destroying queue");
+        unsafe {
+            let status = bindings::hsa_queue_destroy(self.ptr);
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                log_error(&format!(
+                    "Failed to destroy queue: {}",
+                    HsaError::from_status(status)
+                ));
+                // The runtime may still report errors for the queue
+                std::mem::forget(self.error_state.take());
             }
         }
     }
 }
 
+unsafe extern "C" fn queue_error_trampoline(
+    status: bindings::hsa_status_t,
+    source: *mut bindings::hsa_queue_t,
+    data: *mut c_void,
+) {
+    let queue_id = unsafe { (*source).id };
+    let state = unsafe { (data as *const ErrorState).as_ref() };
+    if let Some(state) = state {
+        state.failed.store(true, Ordering::Release);
+    }
+    let callback = state.and_then(|state| state.callback.clone());
+    let user_data = registry()
+        .get_mut(&queue_id)
+        .and_then(|entry| entry.user_data.clone());
+
+    let error = HsaError::from_status(status);
+    log_error(&format!("Queue {} reported an error: {}", queue_id, error));
+
+    if let Some(callback) = callback {
+        callback(&QueueError {
+            queue_id,
+            error,
+            user_data,
+        });
+    }
+}
+
+// Submission only claims slots through the atomic write index and publishes
+// each packet with a release store of its header, so a queue can be shared
+// (e.g. through an `Arc<Queue>`) by several submitting threads.
 unsafe impl Send for Queue {}
 unsafe impl Sync for Queue {}
@@ -1,23 +1,185 @@
 use crate::bindings;
 use crate::error::{log_debug, log_error, log_info};
-use crate::{HsaError, Result};
+use crate::{Agent, DeviceType, HsaError, MachineModel, Result};
+#[cfg(feature = "signal-trace")]
+use std::collections::VecDeque;
+use std::io::{PipeReader, Read, Write};
+use std::marker::PhantomData;
+use std::mem::ManuallyDrop;
+use std::ops::Deref;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, RawFd};
+use std::os::raw::c_void;
 use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const CANCELLABLE_WAIT_CHUNK_NS: u64 = 10_000_000;
+const PROGRESS_WAIT_CHUNK_NS: u64 = 50_000_000;
 
 pub struct Signal {
     handle: bindings::hsa_signal_t,
+    owned: bool,
+    #[cfg(feature = "signal-trace")]
+    trace: Mutex<VecDeque<SignalEvent>>,
+}
+
+/// Comparison a signal wait checks the signal value against.
+///
+/// HSA natively supports `Eq`, `Ne`, `Lt` and `Gte`. `Gt` and `Le` have no
+/// runtime condition of their own; since signal values are integers they are
+/// waited for as `Gte value + 1` and `Lt value + 1`. At `i64::MAX`, where
+/// `value + 1` overflows, `Gt` can never hold and `Le` always holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignalCondition {
+    Eq,
+    Ne,
+    Lt,
+    Gte,
+    Gt,
+    Le,
 }
 
+impl SignalCondition {
+    /// The runtime condition and comparison value to wait with for
+    /// `self` against `value`.
+    pub(crate) fn raw(self, value: i64) -> (bindings::hsa_signal_condition_t, i64) {
+        match (self, value.checked_add(1)) {
+            (SignalCondition::Eq, _) => (
+                bindings::hsa_signal_condition_t_HSA_SIGNAL_CONDITION_EQ,
+                value,
+            ),
+            (SignalCondition::Ne, _) => (
+                bindings::hsa_signal_condition_t_HSA_SIGNAL_CONDITION_NE,
+                value,
+            ),
+            (SignalCondition::Lt, _) => (
+                bindings::hsa_signal_condition_t_HSA_SIGNAL_CONDITION_LT,
+                value,
+            ),
+            (SignalCondition::Gte, _) => (
+                bindings::hsa_signal_condition_t_HSA_SIGNAL_CONDITION_GTE,
+                value,
+            ),
+            (SignalCondition::Gt, Some(next)) => (
+                bindings::hsa_signal_condition_t_HSA_SIGNAL_CONDITION_GTE,
+                next,
+            ),
+            (SignalCondition::Le, Some(next)) => (
+                bindings::hsa_signal_condition_t_HSA_SIGNAL_CONDITION_LT,
+                next,
+            ),
+            // Nothing is below i64::MIN, and everything is at least i64::MIN.
+            (SignalCondition::Gt, None) => (
+                bindings::hsa_signal_condition_t_HSA_SIGNAL_CONDITION_LT,
+                i64::MIN,
+            ),
+            (SignalCondition::Le, None) => (
+                bindings::hsa_signal_condition_t_HSA_SIGNAL_CONDITION_GTE,
+                i64::MIN,
+            ),
+        }
+    }
+}
+
+/// Kind of operation recorded in a signal's trace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignalOp {
+    Load,
+    Store,
+    /// A wait returned; the recorded value is the one observed.
+    Wait,
+    Add,
+    Subtract,
+    Exchange,
+    CompareAndSwap,
+    And,
+    Or,
+    Xor,
+}
+
+/// One entry of a signal's operation trace (`signal-trace` feature).
+#[cfg(feature = "signal-trace")]
+#[derive(Debug, Clone, Copy)]
+pub struct SignalEvent {
+    pub timestamp: Instant,
+    pub op: SignalOp,
+    /// The value loaded or observed for loads and waits, the operand for
+    /// everything else.
+    pub value: i64,
+}
+
+/// Number of events each signal keeps when tracing is enabled.
+#[cfg(feature = "signal-trace")]
+const TRACE_CAPACITY: usize = 256;
+
 impl Signal {
+    /// Create a signal with no specific consumers.
+    ///
+    /// Signal values are 64 bits wide only for agents using the large
+    /// machine model; agents on the small model observe the low 32 bits, so
+    /// a value outside the `i32` range silently wraps there. Use
+    /// [`Signal::create_for_agent`] to have the value checked.
     pub fn create(initial_value: i64) -> Result<Self> {
+        Self::create_with_consumers(initial_value, &[])
+    }
+
+    /// Create `count` signals with no specific consumers, all starting at
+    /// `initial_value`. If any creation fails, the signals already created
+    /// are destroyed before the error is returned.
+    pub fn create_many(count: usize, initial_value: i64) -> Result<Vec<Self>> {
         log_debug(&format!(
-            "Creating signal with initial value: {}",
-            initial_value
+            "Creating {} signals with initial value: {}",
+            count, initial_value
         ));
 
+        let mut signals = Vec::with_capacity(count);
+        for _ in 0..count {
+            match Self::create(initial_value) {
+                Ok(signal) => signals.push(signal),
+                Err(error) => {
+                    log_error(&format!(
+                        "Signal {} of {} could not be created, destroying the rest: {}",
+                        signals.len() + 1,
+                        count,
+                        error.full_message()
+                    ));
+                    // Dropping the vector destroys the signals created so far
+                    drop(signals);
+                    return Err(error);
+                }
+            }
+        }
+        Ok(signals)
+    }
+
+    /// Create a signal that only `consumers` will wait on. Waiting from an
+    /// agent not in the list is undefined behaviour, and on ROCr a host
+    /// wait then busy-spins instead of sleeping on an interrupt, so list the
+    /// CPU agent for anything the host waits on. An empty slice means any
+    /// agent may consume it.
+    pub(crate) fn create_with_consumers(initial_value: i64, consumers: &[Agent]) -> Result<Self> {
+        log_debug(&format!(
+            "Creating signal with initial value: {} ({} consumers)",
+            initial_value,
+            consumers.len()
+        ));
+
+        let consumer_handles: Vec<_> = consumers.iter().map(|a| a.handle).collect();
         let mut signal = bindings::hsa_signal_t { handle: 0 };
 
         unsafe {
-            let status = bindings::hsa_signal_create(initial_value, 0, ptr::null(), &mut signal);
+            let status = bindings::hsa_signal_create(
+                initial_value,
+                consumer_handles.len() as u32,
+                if consumer_handles.is_empty() {
+                    ptr::null()
+                } else {
+                    consumer_handles.as_ptr()
+                },
+                &mut signal,
+            );
 
             if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
                 let error = HsaError::from_status_with_context(
@@ -27,8 +189,8 @@ impl Signal {
                         initial_value
                     ),
                 );
-                log_error(&format!("Signal creation failed: {}", error));
-                return Err(HsaError::SignalOperationFailed(error.to_string()));
+                log_error(&format!("Signal creation failed: {}", error.full_message()));
+                return Err(HsaError::SignalOperationFailed(error.full_message()));
             }
         }
 
@@ -43,14 +205,143 @@ impl Signal {
             signal.handle
         ));
 
-        Ok(Signal { handle: signal })
+        Ok(Signal {
+            handle: signal,
+            owned: true,
+            #[cfg(feature = "signal-trace")]
+            trace: Mutex::new(VecDeque::new()),
+        })
+    }
+
+    /// Create a signal that `agent` will observe, rejecting initial values
+    /// that do not fit the agent's signal value width.
+    pub fn create_for_agent(agent: &Agent, initial_value: i64) -> Result<Self> {
+        if agent.machine_model()? == MachineModel::Small && i32::try_from(initial_value).is_err() {
+            return Err(HsaError::InvalidArgument(format!(
+                "Signal value {} does not fit the 32-bit signal values of agent 0x{:x}",
+                initial_value, agent.handle.handle
+            )));
+        }
+        Self::create(initial_value)
+    }
+
+    /// Create a signal for fork-join style completion counting.
+    ///
+    /// HSA signal values are plain signed integers: every kernel dispatch that
+    /// uses the signal as its completion signal atomically decrements it by
+    /// one when it finishes. Initialising a signal to `count` and sharing it
+    /// between `count` dispatches therefore turns it into a join barrier that
+    /// reaches zero once all of them have completed; see [`Signal::wait_zero`].
+    /// Extra decrements drive the value negative rather than saturating at
+    /// zero, so a negative value means more completions arrived than expected.
+    pub fn create_counter(count: i64) -> Result<Self> {
+        if count < 0 {
+            return Err(HsaError::InvalidArgument(format!(
+                "Signal counter must start at a non-negative value, got {}",
+                count
+            )));
+        }
+        Self::create(count)
+    }
+
+    /// Wait until the signal value reaches zero, returning the observed value.
+    ///
+    /// As with the other wait functions the runtime may return early (on
+    /// timeout or spuriously), so check the returned value.
+    pub fn wait_zero(&self, timeout_ns: u64) -> i64 {
+        let result = self.wait_eq_unrecorded(0, timeout_ns);
+        self.record(SignalOp::Wait, result);
+        result
+    }
+
+    /// Wrap a signal created outside this crate.
+    ///
+    /// If `owned` is true the signal is destroyed when the wrapper is
+    /// dropped; otherwise whoever created it stays responsible for it.
+    ///
+    /// # Safety
+    ///
+    /// `handle` must refer to a live signal that outlives the returned
+    /// wrapper. When `owned` is true nothing else may destroy it.
+    pub unsafe fn from_raw(handle: bindings::hsa_signal_t, owned: bool) -> Self {
+        log_debug(&format!(
+            "Wrapping raw signal 0x{:x} (owned: {})",
+            handle.handle, owned
+        ));
+        Signal {
+            handle,
+            owned,
+            #[cfg(feature = "signal-trace")]
+            trace: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Release the signal handle without destroying the signal. The caller
+    /// becomes responsible for calling `hsa_signal_destroy` on it.
+    pub fn into_raw(self) -> bindings::hsa_signal_t {
+        let signal = ManuallyDrop::new(self);
+        // Forgetting the wrapper would leak the trace's buffer
+        #[cfg(feature = "signal-trace")]
+        drop(std::mem::take(
+            &mut *signal.trace.lock().unwrap_or_else(|e| e.into_inner()),
+        ));
+        signal.handle
     }
 
     pub fn handle(&self) -> bindings::hsa_signal_t {
         self.handle
     }
 
+    /// A non-owning reference to this signal that can be cloned and handed
+    /// to several dispatches and handlers.
+    ///
+    /// The reference never destroys the signal; `self` stays responsible for
+    /// that, and the reference borrows `self` so it cannot outlive it.
+    pub fn clone_handle(&self) -> SignalRef<'_> {
+        SignalRef {
+            signal: unsafe { Signal::from_raw(self.handle, false) },
+            _owner: PhantomData,
+        }
+    }
+
+    /// Load the full 64-bit signal value. This is what agents on the large
+    /// machine model observe; see [`Signal::load32`] for small-model agents.
     pub fn load(&self) -> i64 {
+        let value = self.load_unrecorded();
+        self.record(SignalOp::Load, value);
+        value
+    }
+
+    /// Store a 64-bit signal value. Agents on the small machine model only
+    /// observe the low 32 bits, so use [`Signal::store32`] for signals they
+    /// wait on.
+    pub fn store(&self, value: i64) {
+        self.record(SignalOp::Store, value);
+        self.store_unrecorded(value);
+    }
+
+    /// Load the signal value as an agent on the small machine model sees it,
+    /// i.e. its low 32 bits.
+    ///
+    /// Such an agent decrements only those 32 bits, so after it wraps the
+    /// value through 0 the 64-bit [`Signal::load`] reads a large positive
+    /// number where this returns the expected negative one.
+    pub fn load32(&self) -> i32 {
+        let value = self.load_unrecorded() as i32;
+        self.record(SignalOp::Load, i64::from(value));
+        value
+    }
+
+    /// Store a value that a small machine model agent reads back unchanged.
+    ///
+    /// The value is sign-extended to 64 bits, so host code using
+    /// [`Signal::load`] sees the same value as the agent.
+    pub fn store32(&self, value: i32) {
+        self.record(SignalOp::Store, i64::from(value));
+        self.store_unrecorded(i64::from(value));
+    }
+
+    fn load_unrecorded(&self) -> i64 {
         let value = unsafe { bindings::hsa_signal_load_scacquire(self.handle) };
         log_debug(&format!(
             "Signal 0x{:x} loaded value: {}",
@@ -59,7 +350,7 @@ impl Signal {
         value
     }
 
-    pub fn store(&self, value: i64) {
+    fn store_unrecorded(&self, value: i64) {
         log_debug(&format!(
             "Signal 0x{:x} storing value: {}",
             self.handle.handle, value
@@ -70,6 +361,12 @@ impl Signal {
     }
 
     pub fn wait_eq(&self, value: i64, timeout_ns: u64) -> i64 {
+        let result = self.wait_eq_unrecorded(value, timeout_ns);
+        self.record(SignalOp::Wait, result);
+        result
+    }
+
+    fn wait_eq_unrecorded(&self, value: i64, timeout_ns: u64) -> i64 {
         log_debug(&format!(
             "Signal 0x{:x} waiting for value {} (timeout: {} ns)",
             self.handle.handle, value, timeout_ns
@@ -92,6 +389,81 @@ impl Signal {
         result
     }
 
+    /// Wait until the signal equals `value`, checking `cancel` between
+    /// bounded waits of about 10ms.
+    ///
+    /// Returns `HsaError::ExecutionFailed("cancelled")` once `cancel` is set.
+    /// The flag is only observed between chunks, so cancellation takes effect
+    /// within roughly one chunk (the runtime treats the timeout as a hint).
+    pub fn wait_eq_cancellable(&self, value: i64, cancel: &AtomicBool) -> Result<i64> {
+        log_debug(&format!(
+            "Signal 0x{:x} waiting for value {} (cancellable)",
+            self.handle.handle, value
+        ));
+
+        loop {
+            if cancel.load(Ordering::Acquire) {
+                log_debug(&format!("Signal 0x{:x} wait cancelled", self.handle.handle));
+                return Err(HsaError::ExecutionFailed("cancelled".to_string()));
+            }
+
+            let result = unsafe {
+                bindings::hsa_signal_wait_scacquire(
+                    self.handle,
+                    bindings::hsa_signal_condition_t_HSA_SIGNAL_CONDITION_EQ,
+                    value,
+                    CANCELLABLE_WAIT_CHUNK_NS,
+                    bindings::hsa_wait_state_t_HSA_WAIT_STATE_BLOCKED,
+                )
+            };
+
+            if result == value {
+                log_debug(&format!(
+                    "Signal 0x{:x} wait completed with value: {}",
+                    self.handle.handle, result
+                ));
+                self.record(SignalOp::Wait, result);
+                return Ok(result);
+            }
+        }
+    }
+
+    /// Wait until the signal equals `value`, calling `progress` with the
+    /// current signal value after each bounded wait of about 50ms that ends
+    /// without the condition being met. Returns the observed value.
+    ///
+    /// Useful for kernels that count a progress value down in the signal.
+    /// Like [`Signal::wait_eq_cancellable`] this relies on the runtime
+    /// honouring the timeout, which it treats as a hint.
+    pub fn wait_with_progress(&self, value: i64, mut progress: impl FnMut(i64)) -> i64 {
+        log_debug(&format!(
+            "Signal 0x{:x} waiting for value {} (with progress)",
+            self.handle.handle, value
+        ));
+
+        loop {
+            let result = unsafe {
+                bindings::hsa_signal_wait_scacquire(
+                    self.handle,
+                    bindings::hsa_signal_condition_t_HSA_SIGNAL_CONDITION_EQ,
+                    value,
+                    PROGRESS_WAIT_CHUNK_NS,
+                    bindings::hsa_wait_state_t_HSA_WAIT_STATE_BLOCKED,
+                )
+            };
+
+            if result == value {
+                log_debug(&format!(
+                    "Signal 0x{:x} wait completed with value: {}",
+                    self.handle.handle, result
+                ));
+                self.record(SignalOp::Wait, result);
+                return result;
+            }
+            progress(result);
+        }
+    }
+
     pub fn wait_ne(&self, value: i64, timeout_ns: u64) -> i64 {
         log_debug(&format!(
             "Signal 0x{:x} waiting for value != {} (timeout: {} ns)",
@@ -112,6 +484,7 @@ impl Signal {
             "Signal 0x{:x} wait_ne completed with value: {}",
             self.handle.handle, result
         ));
+        self.record(SignalOp::Wait, result);
         result
     }
 
@@ -135,6 +508,7 @@ impl Signal {
             "Signal 0x{:x} wait_lt completed with value: {}",
             self.handle.handle, result
         ));
+        self.record(SignalOp::Wait, result);
         result
     }
 
@@ -158,14 +532,72 @@ impl Signal {
             "Signal 0x{:x} wait_gte completed with value: {}",
             self.handle.handle, result
         ));
+        self.record(SignalOp::Wait, result);
         result
     }
 
+    /// Wait until the signal value is greater than `value`. See
+    /// [`SignalCondition`] for how this maps onto the runtime's conditions.
+    pub fn wait_gt(&self, value: i64, timeout_ns: u64) -> i64 {
+        self.wait(SignalCondition::Gt, value, timeout_ns)
+    }
+
+    /// Wait until the signal value is less than or equal to `value`. See
+    /// [`SignalCondition`] for how this maps onto the runtime's conditions.
+    pub fn wait_le(&self, value: i64, timeout_ns: u64) -> i64 {
+        self.wait(SignalCondition::Le, value, timeout_ns)
+    }
+
+    /// Wait until the signal value satisfies `condition` against `value`,
+    /// returning the observed value. Like [`Signal::timed_wait`] without the
+    /// duration.
+    pub fn wait(&self, condition: SignalCondition, value: i64, timeout_ns: u64) -> i64 {
+        self.timed_wait(condition, value, timeout_ns).0
+    }
+
+    /// Wait until the signal value satisfies `condition` against `value`,
+    /// returning the observed value together with how long the wait took.
+    ///
+    /// As with the other waits, the returned value may not satisfy the
+    /// condition if the timeout expired or the wait returned spuriously.
+    pub fn timed_wait(
+        &self,
+        condition: SignalCondition,
+        value: i64,
+        timeout_ns: u64,
+    ) -> (i64, Duration) {
+        log_debug(&format!(
+            "Signal 0x{:x} waiting for {:?} {} (timeout: {} ns)",
+            self.handle.handle, condition, value, timeout_ns
+        ));
+
+        let (raw_condition, raw_value) = condition.raw(value);
+        let start = Instant::now();
+        let result = unsafe {
+            bindings::hsa_signal_wait_scacquire(
+                self.handle,
+                raw_condition,
+                raw_value,
+                timeout_ns,
+                bindings::hsa_wait_state_t_HSA_WAIT_STATE_BLOCKED,
+            )
+        };
+        let elapsed = start.elapsed();
+
+        log_debug(&format!(
+            "Signal 0x{:x} wait completed with value {} after {:?}",
+            self.handle.handle, result, elapsed
+        ));
+        self.record(SignalOp::Wait, result);
+        (result, elapsed)
+    }
+
     pub fn add(&self, value: i64) {
         log_debug(&format!(
             "Signal 0x{:x} adding value: {}",
             self.handle.handle, value
         ));
+        self.record(SignalOp::Add, value);
         unsafe {
             bindings::hsa_signal_add_relaxed(self.handle, value);
         }
@@ -176,11 +608,51 @@ impl Signal {
             "Signal 0x{:x} subtracting value: {}",
             self.handle.handle, value
         ));
+        self.record(SignalOp::Subtract, value);
         unsafe {
             bindings::hsa_signal_subtract_relaxed(self.handle, value);
         }
     }
 
+    /// Atomically add `value` and return the value the signal held before.
+    ///
+    /// HSA's add does not return the prior value, so this is a
+    /// compare-and-swap loop with acquire-release ordering.
+    pub fn add_fetch(&self, value: i64) -> i64 {
+        let old_value = self.fetch_update(|current| current.wrapping_add(value));
+        log_debug(&format!(
+            "Signal 0x{:x} add_fetch {}: old value {}",
+            self.handle.handle, value, old_value
+        ));
+        self.record(SignalOp::Add, value);
+        old_value
+    }
+
+    /// Atomically subtract `value` and return the value the signal held
+    /// before. See [`Signal::add_fetch`].
+    pub fn subtract_fetch(&self, value: i64) -> i64 {
+        let old_value = self.fetch_update(|current| current.wrapping_sub(value));
+        log_debug(&format!(
+            "Signal 0x{:x} subtract_fetch {}: old value {}",
+            self.handle.handle, value, old_value
+        ));
+        self.record(SignalOp::Subtract, value);
+        old_value
+    }
+
+    fn fetch_update(&self, update: impl Fn(i64) -> i64) -> i64 {
+        let mut current = unsafe { bindings::hsa_signal_load_relaxed(self.handle) };
+        loop {
+            let observed = unsafe {
+                bindings::hsa_signal_cas_scacq_screl(self.handle, current, update(current))
+            };
+            if observed == current {
+                return current;
+            }
+            current = observed;
+        }
+    }
+
     pub fn exchange(&self, value: i64) -> i64 {
         log_debug(&format!(
             "Signal 0x{:x} exchanging with value: {}",
@@ -191,6 +663,7 @@ impl Signal {
             "Signal 0x{:x} exchange: old value {} -> new value {}",
             self.handle.handle, old_value, value
         ));
+        self.record(SignalOp::Exchange, value);
         old_value
     }
 
@@ -206,6 +679,7 @@ impl Signal {
             old_value,
             old_value == expected
         ));
+        self.record(SignalOp::CompareAndSwap, value);
         old_value
     }
 
@@ -214,6 +688,7 @@ impl Signal {
             "Signal 0x{:x} AND with value: 0x{:x}",
             self.handle.handle, value
         ));
+        self.record(SignalOp::And, value);
         unsafe {
             bindings::hsa_signal_and_relaxed(self.handle, value);
         }
@@ -224,6 +699,7 @@ impl Signal {
             "Signal 0x{:x} OR with value: 0x{:x}",
             self.handle.handle, value
         ));
+        self.record(SignalOp::Or, value);
         unsafe {
             bindings::hsa_signal_or_relaxed(self.handle, value);
         }
@@ -234,11 +710,181 @@ impl Signal {
             "Signal 0x{:x} XOR with value: 0x{:x}",
             self.handle.handle, value
         ));
+        self.record(SignalOp::Xor, value);
         unsafe {
             bindings::hsa_signal_xor_relaxed(self.handle, value);
         }
     }
 
+    /// The most recent operations on this signal made through this wrapper,
+    /// oldest first. Operations by the device or by other handles to the
+    /// same signal are not seen.
+    #[cfg(feature = "signal-trace")]
+    pub fn history(&self) -> Vec<SignalEvent> {
+        let trace = self.trace.lock().unwrap_or_else(|e| e.into_inner());
+        trace.iter().copied().collect()
+    }
+
+    #[cfg(feature = "signal-trace")]
+    fn record(&self, op: SignalOp, value: i64) {
+        let mut trace = self.trace.lock().unwrap_or_else(|e| e.into_inner());
+        if trace.len() == TRACE_CAPACITY {
+            trace.pop_front();
+        }
+        trace.push_back(SignalEvent {
+            timestamp: Instant::now(),
+            op,
+            value,
+        });
+    }
+
+    #[cfg(not(feature = "signal-trace"))]
+    #[inline(always)]
+    fn record(&self, _op: SignalOp, _value: i64) {}
+
+    /// Decrement each of `targets` by one once this signal reaches 0.
+    ///
+    /// A dispatch packet carries a single completion signal; this lets that
+    /// completion notify several downstream stages, each waiting on its own
+    /// signal. The decrements are performed by the runtime's asynchronous
+    /// signal handler thread, not the caller's, so they happen only while
+    /// that thread is running and shortly after the primary signal changes.
+    /// The handler fires at most once. If this signal is destroyed before it
+    /// reaches 0, the handler never runs and `targets` are kept alive until
+    /// the runtime shuts down.
+    pub fn fan_out_to(&self, targets: Vec<Arc<Signal>>) -> Result<()> {
+        log_debug(&format!(
+            "Signal 0x{:x} fanning out to {} signals",
+            self.handle.handle,
+            targets.len()
+        ));
+
+        self.on_condition(
+            bindings::hsa_signal_condition_t_HSA_SIGNAL_CONDITION_EQ,
+            0,
+            move |_| {
+                for target in &targets {
+                    unsafe {
+                        bindings::hsa_signal_subtract_screlease(target.handle, 1);
+                    }
+                }
+            },
+        )
+    }
+
+    /// Consume the signal and deliver its value on a channel once it drops
+    /// to 0 or below, e.g. when a dispatch using it as its completion signal
+    /// finishes.
+    ///
+    /// Exactly one value is sent, from the runtime's asynchronous signal
+    /// handler thread, after which the signal is destroyed (if owned) and the
+    /// sender is dropped. This lets GPU completions be received alongside
+    /// other events in a channel-based event loop.
+    pub fn into_channel(self) -> Result<Receiver<i64>> {
+        let (sender, receiver) = mpsc::channel();
+        let handle = self.handle;
+        let owned = self.owned;
+
+        self.on_condition(
+            bindings::hsa_signal_condition_t_HSA_SIGNAL_CONDITION_LT,
+            1,
+            move |value| {
+                // The receiver may already be gone; the value is then unwanted.
+                let _ = sender.send(value);
+                drop(unsafe { Signal::from_raw(handle, owned) });
+            },
+        )?;
+
+        // The handler now owns the signal.
+        self.into_raw();
+        log_debug(&format!(
+            "Signal 0x{:x} will report completion on a channel",
+            handle.handle
+        ));
+        Ok(receiver)
+    }
+
+    /// Hand the signal to the runtime's asynchronous handler and get a file
+    /// descriptor that becomes readable once the value drops below 1, for
+    /// waiting on GPU completions from an epoll or poll based event loop.
+    ///
+    /// The runtime exposes no pollable descriptor of its own, so the handler
+    /// writes the observed value to a pipe; read it with
+    /// [`SignalFd::read_value`]. As with [`Signal::into_channel`], the signal
+    /// is destroyed (if owned) once it has fired.
+    pub fn into_fd(self) -> Result<SignalFd> {
+        let (reader, mut writer) = std::io::pipe().map_err(|e| {
+            HsaError::SignalOperationFailed(format!("Failed to create notification pipe: {}", e))
+        })?;
+        let handle = self.handle;
+        let owned = self.owned;
+
+        self.on_condition(
+            bindings::hsa_signal_condition_t_HSA_SIGNAL_CONDITION_LT,
+            1,
+            move |value| {
+                // A single write of 8 bytes to an empty pipe does not block.
+                if let Err(e) = writer.write_all(&value.to_ne_bytes()) {
+                    log_error(&format!(
+                        "Failed to notify completion of signal 0x{:x}: {}",
+                        handle.handle, e
+                    ));
+                }
+                drop(unsafe { Signal::from_raw(handle, owned) });
+            },
+        )?;
+
+        // The handler now owns the signal.
+        self.into_raw();
+        log_debug(&format!(
+            "Signal 0x{:x} will report completion on fd {}",
+            handle.handle,
+            reader.as_raw_fd()
+        ));
+        Ok(SignalFd { reader })
+    }
+
+    /// Run `handler` once on the runtime's signal handler thread when the
+    /// signal value satisfies `condition` against `value`. The handler gets
+    /// the observed signal value and must not block.
+    pub(crate) fn on_condition<F>(
+        &self,
+        condition: bindings::hsa_signal_condition_t,
+        value: i64,
+        handler: F,
+    ) -> Result<()>
+    where
+        F: FnOnce(i64) + Send + 'static,
+    {
+        let handler: Box<AsyncHandler> = Box::new(Box::new(handler));
+        let arg = Box::into_raw(handler) as *mut c_void;
+
+        unsafe {
+            let status = bindings::hsa_amd_signal_async_handler(
+                self.handle,
+                condition,
+                value,
+                Some(async_handler_trampoline),
+                arg,
+            );
+
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                drop(Box::from_raw(arg as *mut AsyncHandler));
+                let error = HsaError::from_status_with_context(
+                    status,
+                    "Failed to register asynchronous signal handler",
+                );
+                log_error(&format!(
+                    "Signal handler registration failed: {}",
+                    error.full_message()
+                ));
+                return Err(HsaError::SignalOperationFailed(error.full_message()));
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn print_info(&self) {
         let current_value = self.load();
         log_info(&format!("Signal Information:"));
@@ -247,9 +893,183 @@ impl Signal {
     }
 }
 
+/// Read end of the pipe returned by [`Signal::into_fd`].
+pub struct SignalFd {
+    reader: PipeReader,
+}
+
+impl SignalFd {
+    /// Block until the signal has fired and return the value it was
+    /// observed with. Once the descriptor polls readable this returns
+    /// immediately.
+    pub fn read_value(&mut self) -> std::io::Result<i64> {
+        let mut bytes = [0u8; 8];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(i64::from_ne_bytes(bytes))
+    }
+}
+
+impl AsRawFd for SignalFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.reader.as_raw_fd()
+    }
+}
+
+impl AsFd for SignalFd {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.reader.as_fd()
+    }
+}
+
+/// Non-owning handle to a [`Signal`], created by [`Signal::clone_handle`].
+/// Dereferences to `Signal`, so it can be used wherever a `&Signal` is.
+pub struct SignalRef<'a> {
+    signal: Signal,
+    _owner: PhantomData<&'a Signal>,
+}
+
+impl Clone for SignalRef<'_> {
+    fn clone(&self) -> Self {
+        SignalRef {
+            signal: unsafe { Signal::from_raw(self.signal.handle, false) },
+            _owner: PhantomData,
+        }
+    }
+}
+
+impl Deref for SignalRef<'_> {
+    type Target = Signal;
+
+    fn deref(&self) -> &Signal {
+        &self.signal
+    }
+}
+
+/// Completion signal for a fork-join launch of a known number of dispatches.
+///
+/// The signal starts at `expected` and each dispatch using it as its
+/// completion signal decrements it once. Dereferences to [`Signal`], so it
+/// can be passed straight to a dispatch.
+pub struct JoinSignal {
+    signal: Signal,
+    expected: i64,
+}
+
+impl JoinSignal {
+    pub fn new(expected: i64) -> Result<Self> {
+        Ok(Self {
+            signal: Signal::create_counter(expected)?,
+            expected,
+        })
+    }
+
+    pub fn expected(&self) -> i64 {
+        self.expected
+    }
+
+    /// Check that exactly `expected` completions have arrived. Call this
+    /// after waiting for the signal; an error describes how many dispatches
+    /// are missing or how many extra decrements there were.
+    pub fn verify_complete(&self) -> Result<()> {
+        let remaining = self.signal.load();
+        match remaining {
+            0 => Ok(()),
+            remaining if remaining > 0 => Err(HsaError::ExecutionFailed(format!(
+                "{} of {} expected completions did not arrive",
+                remaining, self.expected
+            ))),
+            remaining => Err(HsaError::ExecutionFailed(format!(
+                "{} completions arrived beyond the {} expected",
+                -remaining, self.expected
+            ))),
+        }
+    }
+}
+
+impl Deref for JoinSignal {
+    type Target = Signal;
+
+    fn deref(&self) -> &Signal {
+        &self.signal
+    }
+}
+
+/// Completion signals recycled between dispatches instead of being created
+/// and destroyed for each one.
+///
+/// The signals are consumed by the host, which waits on them, and by the
+/// agent the pool was created for, which may wait on them in barrier
+/// packets.
+pub struct SignalPool {
+    consumers: Vec<Agent>,
+    free: Mutex<Vec<Signal>>,
+}
+
+impl SignalPool {
+    /// Create a pool holding `capacity` ready signals for the host and
+    /// `agent`. The pool grows past `capacity` on demand.
+    pub fn new(agent: &Agent, capacity: usize) -> Result<Self> {
+        let host = Agent::find(DeviceType::Cpu)?;
+        let mut consumers = vec![host];
+        if *agent != host {
+            consumers.push(*agent);
+        }
+        let mut free = Vec::with_capacity(capacity);
+        for _ in 0..capacity {
+            free.push(Signal::create_with_consumers(1, &consumers)?);
+        }
+
+        log_debug(&format!(
+            "Created signal pool of {} signals for agent 0x{:x}",
+            capacity, agent.handle.handle
+        ));
+
+        Ok(Self {
+            consumers,
+            free: Mutex::new(free),
+        })
+    }
+
+    /// Take a signal from the pool, creating one if it is empty. The signal
+    /// is reset to 1, ready to be used as a dispatch's completion signal.
+    pub fn acquire(&self) -> Result<Signal> {
+        let pooled = self.free.lock().unwrap_or_else(|e| e.into_inner()).pop();
+        match pooled {
+            Some(signal) => {
+                signal.store(1);
+                Ok(signal)
+            }
+            None => Signal::create_with_consumers(1, &self.consumers),
+        }
+    }
+
+    /// Return a signal to the pool. Only release signals whose dispatch has
+    /// completed; the runtime may still write to one that is in flight.
+    pub fn release(&self, signal: Signal) {
+        self.free
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(signal);
+    }
+
+    /// Number of signals ready to be acquired without creating new ones.
+    pub fn available(&self) -> usize {
+        self.free.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+}
+
+type AsyncHandler = Box<dyn FnOnce(i64) + Send>;
+
+unsafe extern "C" fn async_handler_trampoline(value: i64, arg: *mut c_void) -> bool {
+    let handler = unsafe { Box::from_raw(arg as *mut AsyncHandler) };
+    handler(value);
+    // Handlers are one-shot; returning false unregisters this one.
+    false
+}
+
 impl Drop for Signal {
     fn drop(&mut self) {
-        if self.handle.handle != 0 {
+        if self.owned && self.handle.handle != 0 {
             log_debug(&format!("Destroying signal 0x{:x}", self.handle.handle));
             unsafe {
                 let status = bindings::hsa_signal_destroy(self.handle);
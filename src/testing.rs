@@ -0,0 +1,118 @@
+//! Hooks for testing how the crate writes packets into queue rings
+//! (`testing` feature).
+//!
+//! A packet becomes visible to the packet processor the moment its header
+//! turns valid, so the header has to be the last thing written, and written
+//! with release semantics. Every store the crate makes to a slot goes through
+//! a helper in `queue` that reports it here right after it lands, together
+//! with the header as it then reads in memory, so a test can submit a packet
+//! and check both that [`PacketWrite::Header`] came last and that the slot
+//! stayed invalid until it did.
+
+use std::ptr;
+use std::sync::Mutex;
+
+/// One write the crate made to a packet slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketWrite {
+    /// The header was set to `INVALID` and the rest of the slot zeroed.
+    Cleared,
+    /// The packet's fields other than the header were written, or the slot
+    /// was handed out through [`PacketReservation::slot_mut`] for the caller
+    /// to fill in.
+    ///
+    /// [`PacketReservation::slot_mut`]: crate::PacketReservation::slot_mut
+    Body,
+    /// The header and setup words were published with a release store.
+    Header { header: u16 },
+}
+
+/// Called with the address of the slot, the write made to it and the header
+/// read back from the slot once the write landed.
+pub type PacketWriteObserver = fn(slot: usize, write: PacketWrite, header: u16);
+
+static OBSERVER: Mutex<Option<PacketWriteObserver>> = Mutex::new(None);
+
+/// Install `observer`, replacing any previous one, or remove it with `None`.
+/// The observer is process-wide and runs on the submitting thread.
+pub fn set_packet_write_observer(observer: Option<PacketWriteObserver>) {
+    *OBSERVER.lock().unwrap_or_else(|e| e.into_inner()) = observer;
+}
+
+pub(crate) fn notify<T>(slot: *const T, write: PacketWrite) {
+    let observer = *OBSERVER.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(observer) = observer {
+        let header = unsafe { ptr::read_volatile(slot as *const u16) };
+        observer(slot as usize, write, header);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HsaContext;
+    use crate::bindings;
+
+    static WRITES: Mutex<Vec<(usize, PacketWrite, u16)>> = Mutex::new(Vec::new());
+
+    fn record(slot: usize, write: PacketWrite, header: u16) {
+        WRITES
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push((slot, write, header));
+    }
+
+    fn packet_type(header: u16) -> u32 {
+        (header as u32 >> bindings::hsa_packet_header_t_HSA_PACKET_HEADER_TYPE) & 0xff
+    }
+
+    #[test]
+    fn header_is_published_last() {
+        let context = match HsaContext::new() {
+            Ok(context) => context,
+            Err(e) => {
+                eprintln!("skipping: no HSA runtime available ({})", e);
+                return;
+            }
+        };
+        let Some(queue) = context.default_queue() else {
+            eprintln!("skipping: no queue available");
+            return;
+        };
+
+        set_packet_write_observer(Some(record));
+
+        // One reservation committed as a barrier and one dropped, so both the
+        // commit path and the no-op barrier fallback are exercised.
+        let barrier = (bindings::hsa_packet_type_t_HSA_PACKET_TYPE_BARRIER_AND as u16)
+            << bindings::hsa_packet_header_t_HSA_PACKET_HEADER_TYPE;
+        let mut reservation = queue.reserve(1).unwrap();
+        reservation.slot_mut(0);
+        reservation.commit_with_headers(|_| barrier);
+        drop(queue.reserve(1).unwrap());
+
+        set_packet_write_observer(None);
+        let writes = std::mem::take(&mut *WRITES.lock().unwrap_or_else(|e| e.into_inner()));
+
+        let mut slots: Vec<usize> = writes.iter().map(|(slot, _, _)| *slot).collect();
+        slots.dedup();
+        assert_eq!(slots.len(), 2, "unexpected writes: {:?}", writes);
+
+        for slot in slots {
+            let order: Vec<_> = writes.iter().filter(|(s, _, _)| *s == slot).collect();
+            let (last, earlier) = order.split_last().unwrap();
+            assert_eq!(last.1, PacketWrite::Header { header: barrier });
+            assert_eq!(packet_type(last.2), packet_type(barrier));
+            assert_eq!(earlier[0].1, PacketWrite::Cleared);
+            assert!(earlier[1..].iter().all(|(_, w, _)| *w == PacketWrite::Body));
+            for (_, write, header) in earlier {
+                assert_eq!(
+                    packet_type(*header),
+                    bindings::hsa_packet_type_t_HSA_PACKET_TYPE_INVALID,
+                    "slot became valid at {:?}",
+                    write
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,288 @@
+use crate::bindings;
+use crate::error::{log_debug, log_error};
+use crate::{Agent, HsaError, MemoryPool, Result};
+use std::os::raw::c_void;
+use std::ptr;
+
+/// Physical device memory not yet mapped at any address, created from a
+/// memory pool. Map it into a [`VirtualMemory`] reservation to use it.
+pub struct PhysicalMemory {
+    handle: bindings::hsa_amd_vmem_alloc_handle_t,
+    size: usize,
+    agent: Agent,
+}
+
+impl PhysicalMemory {
+    /// Allocate `size` bytes of physical memory from `pool`. `size` must be a
+    /// multiple of [`MemoryPool::alloc_granule`].
+    pub fn create(pool: &MemoryPool, size: usize) -> Result<Self> {
+        let mut handle = bindings::hsa_amd_vmem_alloc_handle_t { handle: 0 };
+
+        unsafe {
+            let status = bindings::hsa_amd_vmem_handle_create(
+                pool.handle,
+                size,
+                bindings::hsa_amd_memory_type_t_MEMORY_TYPE_NONE,
+                0,
+                &mut handle,
+            );
+
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                let error = HsaError::from_status_with_context(
+                    status,
+                    &format!("Failed to create {} bytes of physical memory", size),
+                );
+                log_error(&error.full_message());
+                return Err(HsaError::MemoryAllocationFailed(error.full_message()));
+            }
+        }
+
+        log_debug(&format!(
+            "Created physical memory handle 0x{:x} ({} bytes)",
+            handle.handle, size
+        ));
+
+        Ok(Self {
+            handle,
+            size,
+            agent: pool.agent,
+        })
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+impl Drop for PhysicalMemory {
+    fn drop(&mut self) {
+        let status = unsafe { bindings::hsa_amd_vmem_handle_release(self.handle) };
+        if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+            log_error(&format!(
+                "Failed to release physical memory: {}",
+                HsaError::from_status(status)
+            ));
+        }
+    }
+}
+
+/// A reserved range of device virtual addresses that physical memory can be
+/// mapped into piece by piece, so a buffer can grow in place without being
+/// reallocated and copied.
+///
+/// Offsets and lengths must be multiples of the backing pool's
+/// [allocation granule](MemoryPool::alloc_granule). Everything still mapped
+/// is unmapped and released when the reservation is dropped.
+pub struct VirtualMemory {
+    ptr: *mut c_void,
+    size: usize,
+    mappings: Vec<Mapping>,
+}
+
+struct Mapping {
+    offset: usize,
+    len: usize,
+    // Released only after the range has been unmapped
+    _physical: PhysicalMemory,
+}
+
+impl VirtualMemory {
+    /// Reserve `size` bytes of virtual address space. Nothing is backed by
+    /// memory until it is mapped.
+    pub fn reserve(size: usize) -> Result<Self> {
+        if size == 0 {
+            return Err(HsaError::InvalidArgument(
+                "cannot reserve zero bytes".to_string(),
+            ));
+        }
+
+        let mut ptr = ptr::null_mut();
+        unsafe {
+            let status = bindings::hsa_amd_vmem_address_reserve(&mut ptr, size, 0, 0);
+
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                let error = HsaError::from_status_with_context(
+                    status,
+                    &format!("Failed to reserve {} bytes of virtual address space", size),
+                );
+                log_error(&error.full_message());
+                return Err(HsaError::MemoryAllocationFailed(error.full_message()));
+            }
+        }
+
+        log_debug(&format!(
+            "Reserved {} bytes of virtual address space at {:p}",
+            size, ptr
+        ));
+
+        Ok(Self {
+            ptr,
+            size,
+            mappings: Vec::new(),
+        })
+    }
+
+    pub fn as_ptr(&self) -> *mut c_void {
+        self.ptr
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Map the first `len` bytes of `physical` at `offset` into the
+    /// reservation and give the agent owning its pool read-write access.
+    /// The reservation keeps `physical` until the range is unmapped.
+    pub fn map(&mut self, offset: usize, len: usize, physical: PhysicalMemory) -> Result<()> {
+        self.check_range(offset, len)?;
+        if len > physical.size {
+            return Err(HsaError::InvalidArgument(format!(
+                "Cannot map {} bytes of a {} byte physical allocation",
+                len, physical.size
+            )));
+        }
+        if let Some(mapping) = self
+            .mappings
+            .iter()
+            .find(|m| offset < m.offset + m.len && m.offset < offset + len)
+        {
+            return Err(HsaError::InvalidArgument(format!(
+                "Range at offset {} overlaps the mapping at offset {} ({} bytes)",
+                offset, mapping.offset, mapping.len
+            )));
+        }
+
+        let va = self.address_at(offset);
+        unsafe {
+            let status = bindings::hsa_amd_vmem_map(va, len, 0, physical.handle, 0);
+
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                return Err(HsaError::from_status_with_context(
+                    status,
+                    &format!("Failed to map {} bytes at offset {}", len, offset),
+                ));
+            }
+        }
+
+        if let Err(error) = self.set_access(offset, len, &[physical.agent]) {
+            // Unmap before `physical` is released on return
+            if let Err(e) = self.unmap_raw(offset, len) {
+                log_error(&e.full_message());
+                std::mem::forget(physical);
+            }
+            return Err(error);
+        }
+        self.mappings.push(Mapping {
+            offset,
+            len,
+            _physical: physical,
+        });
+
+        log_debug(&format!(
+            "Mapped {} bytes at {:p} (offset {})",
+            len, va, offset
+        ));
+        Ok(())
+    }
+
+    /// Give `agents` read-write access to a mapped range.
+    pub fn set_access(&self, offset: usize, len: usize, agents: &[Agent]) -> Result<()> {
+        self.check_range(offset, len)?;
+
+        let descriptors: Vec<_> = agents
+            .iter()
+            .map(|agent| bindings::hsa_amd_memory_access_desc_t {
+                permissions: bindings::hsa_access_permission_t_HSA_ACCESS_PERMISSION_RW,
+                agent_handle: agent.handle,
+            })
+            .collect();
+
+        unsafe {
+            let status = bindings::hsa_amd_vmem_set_access(
+                self.address_at(offset),
+                len,
+                descriptors.as_ptr(),
+                descriptors.len(),
+            );
+
+            if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+                return Err(HsaError::from_status_with_context(
+                    status,
+                    &format!(
+                        "Failed to set access for {} bytes at offset {}",
+                        len, offset
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Unmap a range previously passed to [`VirtualMemory::map`] and release
+    /// the physical memory that backed it.
+    pub fn unmap(&mut self, offset: usize, len: usize) -> Result<()> {
+        let Some(index) = self
+            .mappings
+            .iter()
+            .position(|m| m.offset == offset && m.len == len)
+        else {
+            return Err(HsaError::InvalidArgument(format!(
+                "No mapping of {} bytes at offset {}",
+                len, offset
+            )));
+        };
+
+        self.unmap_raw(offset, len)?;
+        self.mappings.swap_remove(index);
+
+        log_debug(&format!("Unmapped {} bytes at offset {}", len, offset));
+        Ok(())
+    }
+
+    fn unmap_raw(&self, offset: usize, len: usize) -> Result<()> {
+        let status = unsafe { bindings::hsa_amd_vmem_unmap(self.address_at(offset), len) };
+        if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+            return Err(HsaError::from_status_with_context(
+                status,
+                &format!("Failed to unmap {} bytes at offset {}", len, offset),
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_range(&self, offset: usize, len: usize) -> Result<()> {
+        match offset.checked_add(len) {
+            Some(end) if len > 0 && end <= self.size => Ok(()),
+            _ => Err(HsaError::InvalidArgument(format!(
+                "Range of {} bytes at offset {} is outside the {} byte reservation",
+                len, offset, self.size
+            ))),
+        }
+    }
+
+    fn address_at(&self, offset: usize) -> *mut c_void {
+        unsafe { (self.ptr as *mut u8).add(offset) as *mut c_void }
+    }
+}
+
+impl Drop for VirtualMemory {
+    fn drop(&mut self) {
+        for mapping in std::mem::take(&mut self.mappings) {
+            if let Err(e) = self.unmap_raw(mapping.offset, mapping.len) {
+                // Releasing memory that is still mapped is not allowed; leak it
+                log_error(&e.full_message());
+                std::mem::forget(mapping);
+            }
+        }
+
+        let status = unsafe { bindings::hsa_amd_vmem_address_free(self.ptr, self.size) };
+        if status != bindings::hsa_status_t_HSA_STATUS_SUCCESS {
+            log_error(&format!(
+                "Failed to free virtual address range: {}",
+                HsaError::from_status(status)
+            ));
+        }
+    }
+}
+
+unsafe impl Send for VirtualMemory {}